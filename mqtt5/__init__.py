@@ -22,3 +22,32 @@ if not typing.TYPE_CHECKING:
         | DisconnectPacket
         | AuthPacket
     )
+    sn.Packet = (
+        sn.AdvertisePacket
+        | sn.SearchGwPacket
+        | sn.GwInfoPacket
+        | sn.ConnectPacket
+        | sn.ConnAckPacket
+        | sn.WillTopicReqPacket
+        | sn.WillTopicPacket
+        | sn.WillMsgReqPacket
+        | sn.WillMsgPacket
+        | sn.RegisterPacket
+        | sn.RegAckPacket
+        | sn.PublishPacket
+        | sn.PubAckPacket
+        | sn.PubCompPacket
+        | sn.PubRecPacket
+        | sn.PubRelPacket
+        | sn.SubscribePacket
+        | sn.SubAckPacket
+        | sn.UnsubscribePacket
+        | sn.UnsubAckPacket
+        | sn.PingReqPacket
+        | sn.PingRespPacket
+        | sn.DisconnectPacket
+        | sn.WillTopicUpdPacket
+        | sn.WillTopicRespPacket
+        | sn.WillMsgUpdPacket
+        | sn.WillMsgRespPacket
+    )
@@ -1,6 +1,8 @@
 import enum
 import typing
 
+from typing_extensions import Buffer
+
 class ConnAckReasonCode(enum.IntEnum):
     SUCCESS = 0
     UNSPECIFIED_ERROR = 128
@@ -114,6 +116,23 @@ class AuthReasonCode(enum.IntEnum):
     CONTINUE_AUTHENTICATION = 24
     RE_AUTHENTICATE = 25
 
+class PacketType(enum.IntEnum):
+    CONNECT = 1
+    CONN_ACK = 2
+    PUBLISH = 3
+    PUB_ACK = 4
+    PUB_REC = 5
+    PUB_REL = 6
+    PUB_COMP = 7
+    SUBSCRIBE = 8
+    SUB_ACK = 9
+    UNSUBSCRIBE = 10
+    UNSUB_ACK = 11
+    PING_REQ = 12
+    PING_RESP = 13
+    DISCONNECT = 14
+    AUTH = 15
+
 class QoS(enum.IntEnum):
     AT_MOST_ONCE = 0
     AT_LEAST_ONCE = 1
@@ -124,12 +143,33 @@ class RetainHandling(enum.IntEnum):
     SEND_IF_SUBSCRIPTION_NOT_EXISTS = 1
     SEND_NEVER = 2
 
+class PayloadFormatIndicator(enum.IntEnum):
+    UNSPECIFIED = 0
+    UTF8 = 1
+
+class MQTTError(ValueError):
+    reason_code: DisconnectReasonCode
+    packet_type: PacketType | None
+    offset: int | None
+
+class MalformedPacketError(MQTTError): ...
+class ProtocolError(MQTTError): ...
+
+class UnsupportedProtocolVersionError(ProtocolError):
+    protocol_name: str
+    protocol_level: int
+    version: typing.Literal[3, 4, 5]
+
+class PacketTooLargeError(MQTTError): ...
+class PayloadFormatInvalidError(MQTTError): ...
+class IncompleteError(MQTTError): ...
+
 class Will:
     topic: str
     payload: bytes | None
     qos: QoS
     retain: bool
-    payload_format_indicator: int
+    payload_format_indicator: PayloadFormatIndicator
     message_expiry_interval: int | None
     content_type: str | None
     response_topic: str | None
@@ -141,14 +181,14 @@ class Will:
         self,
         topic: str,
         *,
-        payload: bytes | None = None,
+        payload: Buffer | None = None,
         qos: QoS = QoS.AT_MOST_ONCE,
         retain: bool = False,
-        payload_format_indicator: int = 0,
+        payload_format_indicator: PayloadFormatIndicator = PayloadFormatIndicator.UNSPECIFIED,
         message_expiry_interval: int | None = None,
         content_type: str | None = None,
         response_topic: str | None = None,
-        correlation_data: bytes | None = None,
+        correlation_data: Buffer | None = None,
         will_delay_interval: int = 0,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
@@ -192,13 +232,13 @@ class ConnectPacket:
         client_id: str,
         *,
         username: str | None = None,
-        password: bytes | None = None,
+        password: Buffer | None = None,
         clean_start: bool = False,
         will: Will | None = None,
         keep_alive: int = 0,
         session_expiry_interval: int = 0,
         authentication_method: str | None = None,
-        authentication_data: bytes | None = None,
+        authentication_data: Buffer | None = None,
         request_problem_info: bool = True,
         request_response_info: bool = False,
         receive_max: int = 65535,
@@ -206,7 +246,20 @@ class ConnectPacket:
         max_packet_size: int | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class ConnAckPacket:
     session_present: bool
@@ -238,7 +291,7 @@ class ConnAckPacket:
         assigned_client_id: str | None = None,
         server_keep_alive: int | None = None,
         authentication_method: str | None = None,
-        authentication_data: bytes | None = None,
+        authentication_data: Buffer | None = None,
         response_info: str | None = None,
         server_reference: str | None = None,
         reason_str: str | None = None,
@@ -252,16 +305,30 @@ class ConnAckPacket:
         shared_subscription_available: bool = True,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PublishPacket:
     topic: str
-    payload: bytes
+    # Kept by reference, writes of payloads other than bytes aren't cached
+    payload: Buffer
     qos: QoS
     retain: bool
     packet_id: int | None
     duplicate: bool
-    payload_format_indicator: int
+    payload_format_indicator: PayloadFormatIndicator
     message_expiry_interval: int | None
     content_type: str | None
     response_topic: str | None
@@ -273,22 +340,48 @@ class PublishPacket:
     def __init__(
         self,
         topic: str,
-        payload: bytes,
+        payload: Buffer,
         *,
         qos: QoS = QoS.AT_MOST_ONCE,
         retain: bool = False,
         packet_id: int | None = None,
         duplicate: bool = False,
-        payload_format_indicator: int = 0,
+        payload_format_indicator: PayloadFormatIndicator = PayloadFormatIndicator.UNSPECIFIED,
         message_expiry_interval: int | None = None,
         content_type: str | None = None,
         response_topic: str | None = None,
-        correlation_data: bytes | None = None,
+        correlation_data: Buffer | None = None,
         subscription_ids: list[int] | None = None,
         topic_alias: int | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def write_vectored(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> list[Buffer]: ...
+    def write_header(
+        self,
+        payload_nbytes: int | None = None,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PubAckPacket:
     packet_id: int
@@ -304,7 +397,20 @@ class PubAckPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PubRecPacket:
     packet_id: int
@@ -320,7 +426,20 @@ class PubRecPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PubRelPacket:
     packet_id: int
@@ -336,7 +455,20 @@ class PubRelPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PubCompPacket:
     packet_id: int
@@ -352,7 +484,20 @@ class PubCompPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class SubscribePacket:
     packet_id: int
@@ -368,7 +513,20 @@ class SubscribePacket:
         subscription_id: int | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class SubAckPacket:
     packet_id: int
@@ -384,7 +542,20 @@ class SubAckPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class UnsubscribePacket:
     packet_id: int
@@ -398,7 +569,20 @@ class UnsubscribePacket:
         *,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class UnsubAckPacket:
     packet_id: int
@@ -414,15 +598,54 @@ class UnsubAckPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PingReqPacket:
     def __init__(self) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class PingRespPacket:
     def __init__(self) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class DisconnectPacket:
     reason_code: DisconnectReasonCode
@@ -440,7 +663,20 @@ class DisconnectPacket:
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
 
 class AuthPacket:
     reason_code: AuthReasonCode
@@ -454,11 +690,44 @@ class AuthPacket:
         *,
         reason_code: AuthReasonCode = AuthReasonCode.SUCCESS,
         authentication_method: str | None = None,
-        authentication_data: bytes | None = None,
+        authentication_data: Buffer | None = None,
         reason_str: str | None = None,
         user_properties: list[tuple[str, str]] | None = None,
     ) -> None: ...
-    def write(self) -> bytes: ...
+    def write(
+        self,
+        *,
+        max_packet_size: int | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> bytes: ...
+    def write_into(
+        self,
+        buffer: bytearray | memoryview,
+        offset: int = 0,
+        *,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> int: ...
+    def nbytes(self, *, version: typing.Literal[3, 4, 5] = 5) -> int: ...
+
+class PublishTemplate:
+    def __init__(
+        self, packet: PublishPacket, *, version: typing.Literal[3, 4, 5] = 5
+    ) -> None: ...
+    def encode(
+        self,
+        payload: Buffer,
+        packet_id: int | None = None,
+        duplicate: bool = False,
+        *,
+        max_packet_size: int | None = None,
+    ) -> bytes: ...
+
+class TopicCache:
+    capacity: int
+
+    def __init__(self, capacity: int = 1024) -> None: ...
+    def clear(self) -> None: ...
+    def __len__(self) -> int: ...
 
 Packet: typing.TypeAlias = (
     ConnectPacket
@@ -478,4 +747,46 @@ Packet: typing.TypeAlias = (
     | AuthPacket
 )
 
-def read(buffer: memoryview) -> tuple[Packet, int]: ...
+class Decoder:
+    def __init__(
+        self,
+        *,
+        strict_strings: bool = False,
+        max_packet_size: int | None = None,
+        check_payload_format: bool = False,
+        topic_cache: TopicCache | None = None,
+        version: typing.Literal[3, 4, 5] = 5,
+    ) -> None: ...
+    def feed(self, data: bytes | bytearray | memoryview) -> None: ...
+    def __iter__(self) -> Decoder: ...
+    def __next__(self) -> Packet: ...
+
+def read(
+    buffer: memoryview,
+    *,
+    strict_strings: bool = False,
+    copy_payload: bool = True,
+    max_packet_size: int | None = None,
+    check_payload_format: bool = False,
+    topic_cache: TopicCache | None = None,
+    version: typing.Literal[3, 4, 5] = 5,
+) -> tuple[Packet, int]: ...
+def read_many(
+    buffer: memoryview,
+    *,
+    strict_strings: bool = False,
+    copy_payload: bool = True,
+    max_packet_size: int | None = None,
+    check_payload_format: bool = False,
+    topic_cache: TopicCache | None = None,
+    version: typing.Literal[3, 4, 5] = 5,
+) -> tuple[list[Packet], int]: ...
+def write_many(
+    packets: typing.Iterable[Packet], *, version: typing.Literal[3, 4, 5] = 5
+) -> bytes: ...
+def peek_header(buffer: memoryview) -> tuple[PacketType, int, int, int] | None: ...
+def sniff_connect(buffer: memoryview) -> tuple[str, int]: ...
+def upgrade(packet: Packet) -> Packet: ...
+def downgrade(
+    packet: Packet, *, topic_aliases: dict[int, str] | None = None
+) -> tuple[Packet, list[str]]: ...
@@ -0,0 +1,328 @@
+import enum
+import typing
+
+from typing_extensions import Buffer
+
+from .mqtt5 import QoS
+
+class PacketType(enum.IntEnum):
+    ADVERTISE = 0
+    SEARCH_GW = 1
+    GW_INFO = 2
+    CONNECT = 4
+    CONN_ACK = 5
+    WILL_TOPIC_REQ = 6
+    WILL_TOPIC = 7
+    WILL_MSG_REQ = 8
+    WILL_MSG = 9
+    REGISTER = 10
+    REG_ACK = 11
+    PUBLISH = 12
+    PUB_ACK = 13
+    PUB_COMP = 14
+    PUB_REC = 15
+    PUB_REL = 16
+    SUBSCRIBE = 18
+    SUB_ACK = 19
+    UNSUBSCRIBE = 20
+    UNSUB_ACK = 21
+    PING_REQ = 22
+    PING_RESP = 23
+    DISCONNECT = 24
+    WILL_TOPIC_UPD = 26
+    WILL_TOPIC_RESP = 27
+    WILL_MSG_UPD = 28
+    WILL_MSG_RESP = 29
+
+class TopicIdType(enum.IntEnum):
+    NORMAL = 0
+    PREDEFINED = 1
+    SHORT_NAME = 2
+
+class ReturnCode(enum.IntEnum):
+    ACCEPTED = 0
+    REJECTED_CONGESTION = 1
+    REJECTED_INVALID_TOPIC_ID = 2
+    REJECTED_NOT_SUPPORTED = 3
+
+class AdvertisePacket:
+    gateway_id: int
+    duration: int
+
+    def __init__(self, gateway_id: int, duration: int) -> None: ...
+    def write(self) -> bytes: ...
+
+class SearchGwPacket:
+    radius: int
+
+    def __init__(self, radius: int) -> None: ...
+    def write(self) -> bytes: ...
+
+class GwInfoPacket:
+    gateway_id: int
+    gateway_address: bytes | None
+
+    def __init__(
+        self, gateway_id: int, gateway_address: Buffer | None = None
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class ConnectPacket:
+    client_id: str
+    duration: int
+    clean_session: bool
+    will: bool
+
+    def __init__(
+        self,
+        client_id: str,
+        *,
+        duration: int = 0,
+        clean_session: bool = False,
+        will: bool = False,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class ConnAckPacket:
+    return_code: ReturnCode
+
+    def __init__(self, *, return_code: ReturnCode = ReturnCode.ACCEPTED) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillTopicReqPacket:
+    def __init__(self) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillTopicPacket:
+    topic: str | None
+    qos: QoS
+    retain: bool
+
+    def __init__(
+        self,
+        topic: str | None = None,
+        *,
+        qos: QoS = QoS.AT_MOST_ONCE,
+        retain: bool = False,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillMsgReqPacket:
+    def __init__(self) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillMsgPacket:
+    payload: bytes
+
+    def __init__(self, payload: Buffer) -> None: ...
+    def write(self) -> bytes: ...
+
+class RegisterPacket:
+    topic_id: int
+    packet_id: int
+    topic: str
+
+    def __init__(self, topic_id: int, packet_id: int, topic: str) -> None: ...
+    def write(self) -> bytes: ...
+
+class RegAckPacket:
+    topic_id: int
+    packet_id: int
+    return_code: ReturnCode
+
+    def __init__(
+        self,
+        topic_id: int,
+        packet_id: int,
+        *,
+        return_code: ReturnCode = ReturnCode.ACCEPTED,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class PublishPacket:
+    topic: int | str
+    payload: bytes
+    topic_id_type: TopicIdType
+    qos: QoS | None
+    retain: bool
+    packet_id: int | None
+    duplicate: bool
+
+    def __init__(
+        self,
+        topic: int | str,
+        payload: Buffer,
+        *,
+        topic_id_type: TopicIdType = TopicIdType.NORMAL,
+        qos: QoS | None = QoS.AT_MOST_ONCE,
+        retain: bool = False,
+        packet_id: int | None = None,
+        duplicate: bool = False,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class PubAckPacket:
+    topic_id: int
+    packet_id: int
+    return_code: ReturnCode
+
+    def __init__(
+        self,
+        topic_id: int,
+        packet_id: int,
+        *,
+        return_code: ReturnCode = ReturnCode.ACCEPTED,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class PubCompPacket:
+    packet_id: int
+
+    def __init__(self, packet_id: int) -> None: ...
+    def write(self) -> bytes: ...
+
+class PubRecPacket:
+    packet_id: int
+
+    def __init__(self, packet_id: int) -> None: ...
+    def write(self) -> bytes: ...
+
+class PubRelPacket:
+    packet_id: int
+
+    def __init__(self, packet_id: int) -> None: ...
+    def write(self) -> bytes: ...
+
+class SubscribePacket:
+    packet_id: int
+    topic: int | str
+    topic_id_type: TopicIdType
+    qos: QoS
+    duplicate: bool
+
+    def __init__(
+        self,
+        packet_id: int,
+        topic: int | str,
+        *,
+        topic_id_type: TopicIdType = TopicIdType.NORMAL,
+        qos: QoS = QoS.AT_MOST_ONCE,
+        duplicate: bool = False,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class SubAckPacket:
+    topic_id: int
+    packet_id: int
+    qos: QoS
+    return_code: ReturnCode
+
+    def __init__(
+        self,
+        topic_id: int,
+        packet_id: int,
+        *,
+        qos: QoS = QoS.AT_MOST_ONCE,
+        return_code: ReturnCode = ReturnCode.ACCEPTED,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class UnsubscribePacket:
+    packet_id: int
+    topic: int | str
+    topic_id_type: TopicIdType
+
+    def __init__(
+        self,
+        packet_id: int,
+        topic: int | str,
+        *,
+        topic_id_type: TopicIdType = TopicIdType.NORMAL,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class UnsubAckPacket:
+    packet_id: int
+
+    def __init__(self, packet_id: int) -> None: ...
+    def write(self) -> bytes: ...
+
+class PingReqPacket:
+    client_id: str | None
+
+    def __init__(self, client_id: str | None = None) -> None: ...
+    def write(self) -> bytes: ...
+
+class PingRespPacket:
+    def __init__(self) -> None: ...
+    def write(self) -> bytes: ...
+
+class DisconnectPacket:
+    duration: int | None
+
+    def __init__(self, duration: int | None = None) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillTopicUpdPacket:
+    topic: str | None
+    qos: QoS
+    retain: bool
+
+    def __init__(
+        self,
+        topic: str | None = None,
+        *,
+        qos: QoS = QoS.AT_MOST_ONCE,
+        retain: bool = False,
+    ) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillTopicRespPacket:
+    return_code: ReturnCode
+
+    def __init__(self, *, return_code: ReturnCode = ReturnCode.ACCEPTED) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillMsgUpdPacket:
+    payload: bytes
+
+    def __init__(self, payload: Buffer) -> None: ...
+    def write(self) -> bytes: ...
+
+class WillMsgRespPacket:
+    return_code: ReturnCode
+
+    def __init__(self, *, return_code: ReturnCode = ReturnCode.ACCEPTED) -> None: ...
+    def write(self) -> bytes: ...
+
+Packet: typing.TypeAlias = (
+    AdvertisePacket
+    | SearchGwPacket
+    | GwInfoPacket
+    | ConnectPacket
+    | ConnAckPacket
+    | WillTopicReqPacket
+    | WillTopicPacket
+    | WillMsgReqPacket
+    | WillMsgPacket
+    | RegisterPacket
+    | RegAckPacket
+    | PublishPacket
+    | PubAckPacket
+    | PubCompPacket
+    | PubRecPacket
+    | PubRelPacket
+    | SubscribePacket
+    | SubAckPacket
+    | UnsubscribePacket
+    | UnsubAckPacket
+    | PingReqPacket
+    | PingRespPacket
+    | DisconnectPacket
+    | WillTopicUpdPacket
+    | WillTopicRespPacket
+    | WillMsgUpdPacket
+    | WillMsgRespPacket
+)
+
+def read(buffer: memoryview) -> tuple[Packet, int]: ...
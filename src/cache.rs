@@ -0,0 +1,87 @@
+use crate::enums::ProtocolVersion;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList};
+use std::sync::Mutex;
+
+/// Packet whose written bytes are cached on the instance.
+pub trait Cached {
+    fn cache(&self) -> &WriteCache;
+    /// Lists of the packet, which can be modified in place despite the packet being frozen.
+    fn lists(&self) -> Vec<&Py<PyList>>;
+}
+
+struct Entry {
+    bytes: Py<PyBytes>,
+    version: ProtocolVersion,
+    /// Items of the packet's lists at the time the bytes were written
+    items: Vec<Vec<Py<PyAny>>>,
+}
+
+/// Bytes that `write` returned, which stay valid as long as the packet's lists hold the same items.
+#[derive(Default)]
+pub struct WriteCache(Mutex<Option<Entry>>);
+
+impl WriteCache {
+    pub fn get(
+        &self,
+        py: Python,
+        lists: &[&Py<PyList>],
+        version: ProtocolVersion,
+    ) -> Option<Py<PyBytes>> {
+        let entry = self.0.lock().ok()?;
+        let entry = entry.as_ref().filter(|entry| entry.version == version)?;
+        // The items themselves are immutable, so comparing their identity is enough
+        let unchanged = lists.iter().zip(&entry.items).all(|(list, items)| {
+            let list = list.bind(py);
+            list.len() == items.len() && list.iter().zip(items).all(|(item, old)| item.is(old))
+        });
+        unchanged.then(|| entry.bytes.clone_ref(py))
+    }
+
+    pub fn set(
+        &self,
+        py: Python,
+        lists: &[&Py<PyList>],
+        bytes: &Py<PyBytes>,
+        version: ProtocolVersion,
+    ) {
+        let items = lists
+            .iter()
+            .map(|list| list.bind(py).iter().map(Bound::unbind).collect())
+            .collect();
+        if let Ok(mut entry) = self.0.lock() {
+            *entry = Some(Entry {
+                bytes: bytes.clone_ref(py),
+                version,
+                items,
+            });
+        }
+    }
+
+    /// Returns the cached bytes if they are valid and fit into the maximum packet size, and
+    /// writes the packet otherwise.
+    pub fn get_or_write(
+        &self,
+        py: Python,
+        lists: &[&Py<PyList>],
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+        write: impl FnOnce() -> PyResult<Py<PyBytes>>,
+    ) -> PyResult<Py<PyBytes>> {
+        if let Some(bytes) = self.get(py, lists, version) {
+            let fits = max_packet_size.is_none_or(|max_packet_size| {
+                bytes.bind(py).as_bytes().len() <= max_packet_size as usize
+            });
+            if fits {
+                return Ok(bytes);
+            }
+        }
+        let bytes = write()?;
+        // Packets written with a maximum packet size may be missing their reason string or user
+        // properties
+        if max_packet_size.is_none() {
+            self.set(py, lists, &bytes, version);
+        }
+        Ok(bytes)
+    }
+}
@@ -1,12 +1,67 @@
+use crate::enums::PayloadFormatIndicator;
+use crate::errors::{MalformedPacketError, PayloadFormatInvalidError};
 use pyo3::PyResult;
-use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyString;
+use pyo3::types::{PyList, PyString};
+
+/// Checks a UTF-8 encoded string against [1.5.4], optionally rejecting the characters that
+/// should not be included as well.
+pub fn check_string_format(s: &str, strict: bool) -> PyResult<()> {
+    if s.contains('\0') {
+        return Err(MalformedPacketError::new_err("Invalid character in string"));
+    }
+    if strict && s.chars().any(|c| c.is_control() || is_noncharacter(c)) {
+        return Err(MalformedPacketError::new_err("Invalid character in string"));
+    }
+    Ok(())
+}
+
+/// Checks the strings of packets that are created in Python like those of packets that are read.
+pub trait CheckFormat {
+    fn check_format(&self, py: Python) -> PyResult<()>;
+}
+
+impl CheckFormat for Py<PyString> {
+    fn check_format(&self, py: Python) -> PyResult<()> {
+        check_string_format(self.bind(py).to_str()?, false)
+    }
+}
+
+impl<T: CheckFormat> CheckFormat for Option<T> {
+    fn check_format(&self, py: Python) -> PyResult<()> {
+        if let Some(v) = self {
+            v.check_format(py)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn check_user_properties_format(py: Python, list: &Py<PyList>) -> PyResult<()> {
+    for item in list.bind(py).iter() {
+        let (key, value): (Py<PyString>, Py<PyString>) = item.extract()?;
+        key.check_format(py)?;
+        value.check_format(py)?;
+    }
+    Ok(())
+}
+
+/// Checks that a payload is well-formed UTF-8 if its payload format indicator says so.
+pub fn check_payload_format(payload: &[u8], indicator: PayloadFormatIndicator) -> PyResult<()> {
+    if indicator == PayloadFormatIndicator::Utf8 && std::str::from_utf8(payload).is_err() {
+        return Err(PayloadFormatInvalidError::new_err("Invalid UTF-8 payload"));
+    }
+    Ok(())
+}
+
+fn is_noncharacter(c: char) -> bool {
+    let c = c as u32;
+    (0xFDD0..=0xFDEF).contains(&c) || c & 0xFFFE == 0xFFFE
+}
 
 pub fn check_topic_format(py: Python, topic: &Py<PyString>) -> PyResult<()> {
     let s = topic.bind(py).to_str()?;
     if s.contains('+') || s.contains('#') {
-        return Err(PyValueError::new_err("Invalid topic"));
+        return Err(MalformedPacketError::new_err("Invalid topic"));
     }
     Ok(())
 }
@@ -16,16 +71,14 @@ pub fn check_pattern_format(py: Python, pattern: &Py<PyString>) -> PyResult<()>
     let bytes = s.as_bytes();
     for (i, &b) in bytes.iter().enumerate() {
         match b {
-            b'#' => {
-                if i != bytes.len() - 1 || (i > 0 && bytes[i - 1] != b'/') {
-                    return Err(PyValueError::new_err("Invalid topic filter"));
-                }
+            b'#' if i != bytes.len() - 1 || (i > 0 && bytes[i - 1] != b'/') => {
+                return Err(MalformedPacketError::new_err("Invalid topic filter"));
             },
             b'+' => {
                 let preceded = i == 0 || bytes[i - 1] == b'/';
                 let followed = i == bytes.len() - 1 || bytes[i + 1] == b'/';
                 if !preceded || !followed {
-                    return Err(PyValueError::new_err("Invalid topic filter"));
+                    return Err(MalformedPacketError::new_err("Invalid topic filter"));
                 }
             },
             _ => {},
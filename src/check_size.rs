@@ -1,6 +1,7 @@
+use crate::errors::{MalformedPacketError, PacketTooLargeError};
 use crate::io::VariableByteInteger;
 use pyo3::PyResult;
-use pyo3::exceptions::PyValueError;
+use pyo3::buffer::PyBuffer;
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyList, PyString};
 
@@ -13,7 +14,7 @@ pub trait CheckSize {
 impl CheckSize for VariableByteInteger {
     fn check_size(&self, _py: Python) -> PyResult<()> {
         if *self >= 1 << 28 {
-            return Err(PyValueError::new_err(
+            return Err(MalformedPacketError::new_err(
                 "Variable byte integer must be < 2**28",
             ));
         }
@@ -24,7 +25,9 @@ impl CheckSize for VariableByteInteger {
 impl CheckSize for Py<PyString> {
     fn check_size(&self, py: Python) -> PyResult<()> {
         if self.bind(py).to_str()?.len() > MAX_FIELD_LENGTH {
-            return Err(PyValueError::new_err("String must be < 65535 bytes"));
+            return Err(MalformedPacketError::new_err(
+                "String must be < 65535 bytes",
+            ));
         }
         Ok(())
     }
@@ -33,7 +36,20 @@ impl CheckSize for Py<PyString> {
 impl CheckSize for Py<PyBytes> {
     fn check_size(&self, py: Python) -> PyResult<()> {
         if self.bind(py).as_bytes().len() > MAX_FIELD_LENGTH {
-            return Err(PyValueError::new_err("Binary data must be < 65535 bytes"));
+            return Err(MalformedPacketError::new_err(
+                "Binary data must be < 65535 bytes",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl CheckSize for PyBuffer<u8> {
+    fn check_size(&self, _py: Python) -> PyResult<()> {
+        if self.len_bytes() > MAX_FIELD_LENGTH {
+            return Err(MalformedPacketError::new_err(
+                "Binary data must be < 65535 bytes",
+            ));
         }
         Ok(())
     }
@@ -48,8 +64,7 @@ impl<T: CheckSize> CheckSize for Option<T> {
     }
 }
 
-pub fn check_user_properties_size(py: Python, list: Option<&Py<PyList>>) -> PyResult<()> {
-    let Some(list) = list else { return Ok(()) };
+pub fn check_user_properties_size(py: Python, list: &Py<PyList>) -> PyResult<()> {
     for item in list.bind(py).iter() {
         let (key, value): (Py<PyString>, Py<PyString>) = item.extract()?;
         key.check_size(py)?;
@@ -57,3 +72,13 @@ pub fn check_user_properties_size(py: Python, list: Option<&Py<PyList>>) -> PyRe
     }
     Ok(())
 }
+
+/// Checks that a packet of the given size doesn't exceed the maximum packet size.
+pub fn check_packet_size(nbytes: usize, max_packet_size: Option<u32>) -> PyResult<()> {
+    if max_packet_size.is_some_and(|max_packet_size| nbytes > max_packet_size as usize) {
+        return Err(PacketTooLargeError::new_err(
+            "Packet size exceeds maximum packet size",
+        ));
+    }
+    Ok(())
+}
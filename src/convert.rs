@@ -0,0 +1,295 @@
+use crate::enums::*;
+use crate::frame::*;
+use crate::io::VariableByteInteger;
+use crate::packets::*;
+use pyo3::PyResult;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyMemoryView, PyString};
+
+/// Names of the fields whose values were lost in a conversion.
+type Lost = Vec<&'static str>;
+
+/// Notes that the value of a field was lost, once per field.
+fn note(lost: &mut Lost, field: &'static str) {
+    if !lost.contains(&field) {
+        lost.push(field);
+    }
+}
+
+/// Resets fields that MQTT 3.1.1 lacks to their defaults, noting those that had other values.
+macro_rules! drop_fields {
+    ($lost:expr, $frame:expr, $prefix:literal, { $($field:ident = $default:expr),* $(,)? }) => {
+        $(
+            if $frame.$field != $default {
+                $frame.$field = $default;
+                note($lost, concat!($prefix, stringify!($field)));
+            }
+        )*
+    };
+}
+
+/// Session expiry interval that corresponds to the clean session flag of MQTT 3.1.1.
+fn session_expiry_interval(clean_start: bool) -> u32 {
+    // Sessions without clean start are kept until the client cleans them up again
+    if clean_start { 0 } else { u32::MAX }
+}
+
+fn downgrade_connect(frame: &mut ConnectFrame, lost: &mut Lost) {
+    if frame.session_expiry_interval != session_expiry_interval(frame.clean_start) {
+        note(lost, "session_expiry_interval");
+    }
+    frame.session_expiry_interval = 0;
+    // MQTT 3.1.1 requires a username to send a password
+    if frame.username.is_none() && frame.password.is_some() {
+        frame.password = None;
+        note(lost, "password");
+    }
+    if let Some(will) = &mut frame.will {
+        drop_fields!(lost, will, "will.", {
+            payload_format_indicator = PayloadFormatIndicator::Unspecified,
+            message_expiry_interval = None,
+            content_type = None,
+            response_topic = None,
+            correlation_data = None,
+            will_delay_interval = 0,
+            user_properties = Vec::new(),
+        });
+    }
+    drop_fields!(lost, frame, "", {
+        authentication_method = None,
+        authentication_data = None,
+        request_problem_info = true,
+        request_response_info = false,
+        receive_max = 65535,
+        topic_alias_max = 0,
+        max_packet_size = None,
+        user_properties = Vec::new(),
+    });
+}
+
+fn downgrade_connack(frame: &mut ConnAckFrame, lost: &mut Lost) {
+    drop_fields!(lost, frame, "", {
+        reason_code = frame.reason_code.downgrade(),
+        session_expiry_interval = None,
+        assigned_client_id = None,
+        server_keep_alive = None,
+        authentication_method = None,
+        authentication_data = None,
+        response_info = None,
+        server_reference = None,
+        reason_str = None,
+        receive_max = 65535,
+        topic_alias_max = 0,
+        max_qos = QoS::ExactlyOnce,
+        retain_available = true,
+        max_packet_size = None,
+        wildcard_subscription_available = true,
+        subscription_id_available = true,
+        shared_subscription_available = true,
+        user_properties = Vec::new(),
+    });
+}
+
+fn downgrade_publish(frame: &mut PublishFrame, lost: &mut Lost) {
+    drop_fields!(lost, frame, "", {
+        payload_format_indicator = PayloadFormatIndicator::Unspecified,
+        message_expiry_interval = None,
+        content_type = None,
+        response_topic = None,
+        correlation_data = None,
+        subscription_ids = Vec::<VariableByteInteger>::new(),
+        user_properties = Vec::new(),
+    });
+}
+
+fn downgrade_subscribe(frame: &mut SubscribeFrame, lost: &mut Lost) {
+    for topic_filter in &mut frame.topic_filters {
+        drop_fields!(lost, topic_filter, "topic_filters.", {
+            no_local = false,
+            retain_as_published = true,
+            retain_handling = RetainHandling::SendAlways,
+        });
+    }
+    drop_fields!(lost, frame, "", {
+        subscription_id = None,
+        user_properties = Vec::new(),
+    });
+}
+
+fn downgrade_suback(frame: &mut SubAckFrame, lost: &mut Lost) {
+    for reason_code in &mut frame.reason_codes {
+        if *reason_code != reason_code.downgrade() {
+            *reason_code = reason_code.downgrade();
+            note(lost, "reason_codes");
+        }
+    }
+    drop_fields!(lost, frame, "", {
+        reason_str = None,
+        user_properties = Vec::new(),
+    });
+}
+
+fn downgrade_unsuback(frame: &mut UnsubAckFrame, lost: &mut Lost) {
+    // MQTT 3.1.1 acknowledges unsubscribing without reason codes
+    if frame
+        .reason_codes
+        .iter()
+        .any(|reason_code| *reason_code != UnsubAckReasonCode::Success)
+    {
+        note(lost, "reason_codes");
+    }
+    frame.reason_codes.clear();
+    drop_fields!(lost, frame, "", {
+        reason_str = None,
+        user_properties = Vec::new(),
+    });
+}
+
+/// Replaces the topic alias of a publish packet with the topic that it stands for.
+///
+/// Topic aliases are scoped to the connection, so the mapping is kept in a dictionary that the
+/// caller passes along with each packet of the connection.
+fn resolve_topic_alias<'py>(
+    py: Python<'py>,
+    packet: &PublishPacket,
+    topic_aliases: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Option<Bound<'py, PyString>>> {
+    let Some(topic_alias) = packet.topic_alias else {
+        return Ok(None);
+    };
+    // The mapping is passed in by the caller, so a missing alias is a bad argument
+    let unknown = || PyValueError::new_err(format!("Unknown topic alias: {topic_alias}"));
+    // Packets that carry their topic don't need the mapping, they only update it
+    if !packet.topic.bind(py).to_str()?.is_empty() {
+        if let Some(topic_aliases) = topic_aliases {
+            topic_aliases.set_item(topic_alias, &packet.topic)?;
+        }
+        return Ok(None);
+    }
+    match topic_aliases.ok_or_else(unknown)?.get_item(topic_alias)? {
+        Some(topic) => Ok(Some(topic.cast_into::<PyString>()?)),
+        None => Err(unknown()),
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (packet, *, topic_aliases=None))]
+pub fn downgrade(
+    py: Python,
+    packet: &Bound<'_, PyAny>,
+    topic_aliases: Option<&Bound<'_, PyDict>>,
+) -> PyResult<(Py<PyAny>, Lost)> {
+    let mut lost = Lost::new();
+    let packet_ref = PacketRef::extract(packet)?;
+    let topic = match packet_ref {
+        PacketRef::Publish(packet) => resolve_topic_alias(py, packet, topic_aliases)?,
+        _ => None,
+    };
+    let backing = packet_ref.backing(py)?;
+    let packet = match packet_ref.frame(py, &backing)? {
+        Frame::Connect(mut frame) => {
+            downgrade_connect(&mut frame, &mut lost);
+            ConnectPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::ConnAck(mut frame) => {
+            downgrade_connack(&mut frame, &mut lost);
+            ConnAckPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::Publish(mut frame) => {
+            downgrade_publish(&mut frame, &mut lost);
+            if let Some(topic) = &topic {
+                frame.topic = topic.to_str()?;
+            }
+            frame.topic_alias = None;
+            // Slicing the whole payload keeps bytes objects as they are and views other buffers
+            let payload = packet.getattr("payload")?;
+            let source = if payload.is_instance_of::<PyBytes>() {
+                payload
+            } else {
+                PyMemoryView::from(&payload)?.call_method1("cast", ("B",))?
+            };
+            PublishPacket::from_frame(py, frame, Some(&source.unbind()), None)?.into_any()
+        },
+        Frame::PubAck(mut frame) => {
+            drop_fields!(&mut lost, frame, "", {
+                reason_code = PubAckReasonCode::Success,
+                reason_str = None,
+                user_properties = Vec::new(),
+            });
+            PubAckPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::PubRec(mut frame) => {
+            drop_fields!(&mut lost, frame, "", {
+                reason_code = PubRecReasonCode::Success,
+                reason_str = None,
+                user_properties = Vec::new(),
+            });
+            PubRecPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::PubRel(mut frame) => {
+            drop_fields!(&mut lost, frame, "", {
+                reason_code = PubRelReasonCode::Success,
+                reason_str = None,
+                user_properties = Vec::new(),
+            });
+            PubRelPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::PubComp(mut frame) => {
+            drop_fields!(&mut lost, frame, "", {
+                reason_code = PubCompReasonCode::Success,
+                reason_str = None,
+                user_properties = Vec::new(),
+            });
+            PubCompPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::Subscribe(mut frame) => {
+            downgrade_subscribe(&mut frame, &mut lost);
+            SubscribePacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::SubAck(mut frame) => {
+            downgrade_suback(&mut frame, &mut lost);
+            SubAckPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::Unsubscribe(mut frame) => {
+            drop_fields!(&mut lost, frame, "", {
+                user_properties = Vec::new(),
+            });
+            UnsubscribePacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::UnsubAck(mut frame) => {
+            downgrade_unsuback(&mut frame, &mut lost);
+            UnsubAckPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::PingReq(frame) => PingReqPacket::from_frame(py, frame)?.into_any(),
+        Frame::PingResp(frame) => PingRespPacket::from_frame(py, frame)?.into_any(),
+        Frame::Disconnect(mut frame) => {
+            drop_fields!(&mut lost, frame, "", {
+                reason_code = DisconnectReasonCode::NormalDisconnection,
+                session_expiry_interval = None,
+                server_reference = None,
+                reason_str = None,
+                user_properties = Vec::new(),
+            });
+            DisconnectPacket::from_frame(py, frame)?.into_any()
+        },
+        Frame::Auth(_) => {
+            return Err(PyTypeError::new_err(
+                "Auth packet is not supported before MQTT 5",
+            ));
+        },
+    };
+    Ok((packet, lost))
+}
+
+#[pyfunction]
+pub fn upgrade(py: Python, packet: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+    // The clean session flag of MQTT 3.1.1 also decides how long the session is kept
+    let PacketRef::Connect(connect) = PacketRef::extract(packet)? else {
+        return Ok(packet.clone().unbind());
+    };
+    let backing = connect.backing(py)?;
+    let mut frame = connect.frame(py, &backing)?;
+    frame.session_expiry_interval = session_expiry_interval(frame.clean_start);
+    Ok(ConnectPacket::from_frame(py, frame)?.into_any())
+}
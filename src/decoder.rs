@@ -0,0 +1,105 @@
+use crate::enums::ProtocolVersion;
+use crate::errors::locate;
+use crate::io::{FixedHeader, ReadCursor, ReadOptions, buffer_bytes};
+use crate::read_packet;
+use crate::topic_cache::TopicCache;
+use pyo3::PyResult;
+use pyo3::buffer::PyBuffer;
+use pyo3::prelude::*;
+
+#[pyclass(module = "mqtt5")]
+pub struct Decoder {
+    buffer: Vec<u8>,
+    /// Start of the bytes that haven't been decoded yet
+    index: usize,
+    /// Fixed header of the pending packet, together with its size
+    header: Option<(FixedHeader, usize)>,
+    options: ReadOptions,
+    topic_cache: Option<Py<TopicCache>>,
+}
+
+#[pymethods]
+impl Decoder {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        strict_strings=false,
+        max_packet_size=None,
+        check_payload_format=false,
+        topic_cache=None,
+        version=ProtocolVersion::V5,
+    ))]
+    pub fn new(
+        strict_strings: bool,
+        max_packet_size: Option<u32>,
+        check_payload_format: bool,
+        topic_cache: Option<Py<TopicCache>>,
+        version: ProtocolVersion,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            buffer: Vec::new(),
+            index: 0,
+            header: None,
+            options: ReadOptions::new(
+                strict_strings,
+                max_packet_size,
+                check_payload_format,
+                version,
+            )?,
+            topic_cache,
+        })
+    }
+
+    pub fn feed(&mut self, data: PyBuffer<u8>) -> PyResult<()> {
+        // Drop the bytes of packets that we already returned
+        if self.index > 0 {
+            self.buffer.drain(..self.index);
+            self.index = 0;
+        }
+        self.buffer.extend_from_slice(buffer_bytes(&data)?);
+        Ok(())
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        let pending = &self.buffer[self.index..];
+        // Parse the fixed header only once per packet
+        let (header, header_nbytes) = match self.header {
+            Some(header) => header,
+            None => {
+                let mut cursor = ReadCursor::new(pending);
+                match FixedHeader::peek(&mut cursor)
+                    .map_err(|error| locate(py, error, None, Some(cursor.index)))?
+                {
+                    Some(header) => {
+                        // Fail before buffering the body of a packet that's too large
+                        header
+                            .check_packet_size(cursor.index, &self.options)
+                            .map_err(|error| {
+                                locate(py, error, Some(header.packet_type), Some(cursor.index))
+                            })?;
+                        *self.header.insert((header, cursor.index))
+                    },
+                    None => return Ok(None),
+                }
+            },
+        };
+        let nbytes = header_nbytes + usize::from(header.remaining_length);
+        if pending.len() < nbytes {
+            return Ok(None);
+        }
+        // Consume the packet before reading it, such that a malformed packet is skipped
+        self.index += nbytes;
+        self.header = None;
+        let mut cursor = ReadCursor::new(pending);
+        cursor.index = header_nbytes;
+        cursor.options = self.options;
+        cursor.topic_cache = self.topic_cache.as_ref().map(Py::get);
+        cursor.bind(header.remaining_length.into())?;
+        // The pending bytes are owned by the decoder, so they can't change while parsing
+        read_packet(py, &mut cursor, &header, 0, true).map(Some)
+    }
+}
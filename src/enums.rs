@@ -1,3 +1,4 @@
+use crate::errors::{MalformedPacketError, ProtocolError, unsupported_protocol_version};
 use crate::io::{ReadCursor, Readable, Writable, WriteCursor};
 use num_enum::TryFromPrimitive;
 use pyo3::PyResult;
@@ -5,33 +6,6 @@ use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use std::fmt;
 
-#[derive(PartialEq, Eq, TryFromPrimitive)]
-#[repr(u8)]
-pub enum PacketType {
-    Connect = 1,
-    ConnAck = 2,
-    Publish = 3,
-    PubAck = 4,
-    PubRec = 5,
-    PubRel = 6,
-    PubComp = 7,
-    Subscribe = 8,
-    SubAck = 9,
-    Unsubscribe = 10,
-    UnsubAck = 11,
-    PingReq = 12,
-    PingResp = 13,
-    Disconnect = 14,
-    Auth = 15,
-}
-
-impl PacketType {
-    pub fn new(value: u8) -> PyResult<Self> {
-        Self::try_from(value)
-            .map_err(|_| PyValueError::new_err(format!("Invalid PacketType value: {value}")))
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq, TryFromPrimitive)]
 #[repr(u8)]
 pub enum PropertyType {
@@ -65,15 +39,63 @@ pub enum PropertyType {
 }
 
 impl PropertyType {
+    pub fn new(value: u8) -> PyResult<Self> {
+        Self::try_from(value).map_err(|_| {
+            MalformedPacketError::new_err(format!("Invalid PropertyType value: {value}"))
+        })
+    }
+}
+
+/// Version of the protocol that packets are read and written with.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum ProtocolVersion {
+    V31 = 3,
+    V311 = 4,
+    #[default]
+    V5 = 5,
+}
+
+impl ProtocolVersion {
     pub fn new(value: u8) -> PyResult<Self> {
         Self::try_from(value)
-            .map_err(|_| PyValueError::new_err(format!("Invalid PropertyType value: {value}")))
+            .map_err(|_| PyValueError::new_err(format!("Invalid protocol version: {value}")))
+    }
+
+    /// Returns the version that the protocol name and level of a connect packet stand for.
+    pub fn from_protocol(protocol_name: &[u8], protocol_level: u8) -> PyResult<Self> {
+        if protocol_name != b"MQTT" && protocol_name != b"MQIsdp" {
+            return Err(ProtocolError::new_err("Invalid protocol name"));
+        }
+        match Self::try_from(protocol_level) {
+            Ok(version) if version.protocol_name().as_bytes() == protocol_name => Ok(version),
+            _ => Err(unsupported_protocol_version(protocol_name, protocol_level)),
+        }
+    }
+
+    /// Name of the protocol in the connect packet's variable header.
+    pub fn protocol_name(self) -> &'static str {
+        match self {
+            Self::V31 => "MQIsdp",
+            Self::V311 | Self::V5 => "MQTT",
+        }
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for ProtocolVersion {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        Self::new(obj.extract()?)
     }
 }
 
 macro_rules! py_int_enum {
     ( $name:ident { $($field:ident = $value:expr),* $(,)? } ) => {
-        #[pyclass(eq, eq_int, str, from_py_object, rename_all = "SCREAMING_SNAKE_CASE", module = "mqtt5")]
+        py_int_enum!("mqtt5", $name { $($field = $value),* });
+    };
+    ( $module:literal, $name:ident { $($field:ident = $value:expr),* $(,)? } ) => {
+        #[pyclass(eq, eq_int, str, from_py_object, rename_all = "SCREAMING_SNAKE_CASE", module = $module)]
         #[derive(Copy, Clone, PartialEq, Eq, TryFromPrimitive)]
         #[repr(u8)]
         pub enum $name {
@@ -86,7 +108,7 @@ macro_rules! py_int_enum {
             pub fn new(value: u8) -> PyResult<Self> {
                 Self::try_from(value)
                     .map_err(|_| {
-                        PyValueError::new_err(format!(
+                        MalformedPacketError::new_err(format!(
                             "Invalid {} value: {value}",
                             stringify!($name)
                         ))
@@ -140,8 +162,8 @@ macro_rules! py_int_enum {
             }
         }
 
-        impl Readable for $name {
-            fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        impl<'a> Readable<'a> for $name {
+            fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
                 cursor.require(1)?;
                 let result = Self::new(cursor.buffer[cursor.index])?;
                 cursor.index += 1;
@@ -162,6 +184,28 @@ macro_rules! py_int_enum {
     };
 }
 
+pub(crate) use py_int_enum;
+
+py_int_enum! {
+    PacketType {
+        Connect = 1,
+        ConnAck = 2,
+        Publish = 3,
+        PubAck = 4,
+        PubRec = 5,
+        PubRel = 6,
+        PubComp = 7,
+        Subscribe = 8,
+        SubAck = 9,
+        Unsubscribe = 10,
+        UnsubAck = 11,
+        PingReq = 12,
+        PingResp = 13,
+        Disconnect = 14,
+        Auth = 15,
+    }
+}
+
 py_int_enum! {
     QoS {
         AtMostOnce = 0,
@@ -178,6 +222,13 @@ py_int_enum! {
     }
 }
 
+py_int_enum! {
+    PayloadFormatIndicator {
+        Unspecified = 0,
+        Utf8 = 1,
+    }
+}
+
 py_int_enum! {
     ConnAckReasonCode {
         Success = 0,
@@ -317,3 +368,65 @@ py_int_enum! {
         WildcardSubscriptionsNotSupported = 162,
     }
 }
+
+impl ConnAckReasonCode {
+    /// Returns the MQTT 3.1.1 return code that corresponds to the reason code, if there is one.
+    pub fn return_code(self) -> Option<u8> {
+        match self {
+            Self::Success => Some(0),
+            Self::UnsupportedProtocolVersion => Some(1),
+            Self::ClientIdNotValid => Some(2),
+            Self::ServerUnavailable => Some(3),
+            Self::BadUserNameOrPassword => Some(4),
+            Self::NotAuthorized => Some(5),
+            _ => None,
+        }
+    }
+
+    /// Returns the reason code that corresponds to the MQTT 3.1.1 return code.
+    pub fn from_return_code(value: u8) -> PyResult<Self> {
+        match value {
+            0 => Ok(Self::Success),
+            1 => Ok(Self::UnsupportedProtocolVersion),
+            2 => Ok(Self::ClientIdNotValid),
+            3 => Ok(Self::ServerUnavailable),
+            4 => Ok(Self::BadUserNameOrPassword),
+            5 => Ok(Self::NotAuthorized),
+            _ => Err(MalformedPacketError::new_err(format!(
+                "Invalid ConnAck return code: {value}"
+            ))),
+        }
+    }
+
+    /// Returns the closest reason code that has an MQTT 3.1.1 return code.
+    pub fn downgrade(self) -> Self {
+        match self {
+            _ if self.return_code().is_some() => self,
+            Self::BadAuthenticationMethod => Self::BadUserNameOrPassword,
+            Self::Banned => Self::NotAuthorized,
+            _ => Self::ServerUnavailable,
+        }
+    }
+}
+
+impl SubAckReasonCode {
+    /// Checks whether the reason code is one of the return codes of MQTT 3.1.1.
+    pub fn is_return_code(self) -> bool {
+        matches!(
+            self,
+            Self::GrantedQosAtMostOnce
+                | Self::GrantedQosAtLeastOnce
+                | Self::GrantedQosExactlyOnce
+                | Self::UnspecifiedError
+        )
+    }
+
+    /// Returns the reason code, or the MQTT 3.1.1 failure return code if it isn't a return code.
+    pub fn downgrade(self) -> Self {
+        if self.is_return_code() {
+            self
+        } else {
+            Self::UnspecifiedError
+        }
+    }
+}
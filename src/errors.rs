@@ -0,0 +1,122 @@
+use crate::enums::{DisconnectReasonCode, PacketType, ProtocolVersion};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyBaseException, PyValueError};
+use pyo3::prelude::*;
+
+create_exception!(
+    mqtt5,
+    MQTTError,
+    PyValueError,
+    "Base class for errors from reading or creating packets."
+);
+create_exception!(
+    mqtt5,
+    MalformedPacketError,
+    MQTTError,
+    "The packet cannot be parsed according to the specification."
+);
+create_exception!(
+    mqtt5,
+    ProtocolError,
+    MQTTError,
+    "The packet can be parsed but contains data that the specification doesn't allow."
+);
+create_exception!(
+    mqtt5,
+    UnsupportedProtocolVersionError,
+    ProtocolError,
+    "The connect packet has a protocol name or level that isn't supported."
+);
+create_exception!(
+    mqtt5,
+    PacketTooLargeError,
+    MQTTError,
+    "The packet is larger than the maximum packet size."
+);
+create_exception!(
+    mqtt5,
+    PayloadFormatInvalidError,
+    MQTTError,
+    "The payload doesn't match its payload format indicator."
+);
+create_exception!(
+    mqtt5,
+    IncompleteError,
+    MQTTError,
+    "The buffer doesn't hold the complete packet yet. Its reason code only applies if the stream ends before the packet is complete."
+);
+
+/// Sets the class attributes that describe the errors to the user.
+pub fn add_errors(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let base = py.get_type::<MQTTError>();
+    base.setattr("reason_code", DisconnectReasonCode::UnspecifiedError)?;
+    base.setattr("packet_type", py.None())?;
+    base.setattr("offset", py.None())?;
+    #[rustfmt::skip]
+    let errors = [
+        (py.get_type::<MalformedPacketError>(), DisconnectReasonCode::MalformedPacket),
+        (py.get_type::<ProtocolError>(), DisconnectReasonCode::ProtocolError),
+        (py.get_type::<UnsupportedProtocolVersionError>(), DisconnectReasonCode::ProtocolError),
+        (py.get_type::<PacketTooLargeError>(), DisconnectReasonCode::PacketTooLarge),
+        (py.get_type::<PayloadFormatInvalidError>(), DisconnectReasonCode::PayloadFormatInvalid),
+        (py.get_type::<IncompleteError>(), DisconnectReasonCode::MalformedPacket),
+    ];
+    m.add("MQTTError", base)?;
+    for (error, reason_code) in errors {
+        error.setattr("reason_code", reason_code)?;
+        m.add(error.name()?, error)?;
+    }
+    Ok(())
+}
+
+/// Creates the error for a connect packet whose protocol name and level aren't supported.
+///
+/// Reading can fail without holding the GIL, so the protocol name and level travel as arguments
+/// of the error until `locate` turns them into attributes.
+pub fn unsupported_protocol_version(protocol_name: &[u8], protocol_level: u8) -> PyErr {
+    // Answer with the peer's version where it's known, and the MQTT 3.1.1 layout otherwise
+    let version = match (protocol_name, protocol_level) {
+        (b"MQIsdp", _) => ProtocolVersion::V31,
+        (_, 5) => ProtocolVersion::V5,
+        _ => ProtocolVersion::V311,
+    };
+    UnsupportedProtocolVersionError::new_err((
+        "Invalid protocol version",
+        String::from_utf8_lossy(protocol_name).into_owned(),
+        protocol_level,
+        version as u8,
+    ))
+}
+
+/// Moves the protocol name, protocol level, and version to write the connect acknowledgement
+/// with from the arguments of the error to its attributes.
+fn describe_protocol(value: &Bound<'_, PyBaseException>) -> PyResult<()> {
+    let (message, protocol_name, protocol_level, version): (String, String, u8, u8) =
+        value.getattr("args")?.extract()?;
+    value.setattr("protocol_name", protocol_name)?;
+    value.setattr("protocol_level", protocol_level)?;
+    value.setattr("version", version)?;
+    value.setattr("args", (message,))
+}
+
+/// Attaches the packet type and the byte offset at which reading failed to the error.
+///
+/// The offset is `None` for errors that concern the packet as a whole rather than the field at
+/// the cursor, such as validation after parsing.
+pub fn locate(
+    py: Python,
+    error: PyErr,
+    packet_type: Option<PacketType>,
+    offset: Option<usize>,
+) -> PyErr {
+    if error.is_instance_of::<MQTTError>(py) {
+        let value = error.value(py);
+        let _ = value.setattr("packet_type", packet_type);
+        let _ = value.setattr("offset", offset);
+        if error.is_instance_of::<UnsupportedProtocolVersionError>(py) {
+            let _ = describe_protocol(value);
+        }
+    }
+    error
+}
@@ -0,0 +1,2050 @@
+use crate::check_format::check_payload_format;
+use crate::enums::*;
+use crate::errors::{MalformedPacketError, ProtocolError, unsupported_protocol_version};
+use crate::io::{FixedHeader, ReadCursor, Readable, VariableByteInteger, Writable, WriteCursor};
+use pyo3::PyResult;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList, PyString};
+
+macro_rules! read_properties {
+    ($packet_name:literal, $cursor:expr, {
+        $($property_type:path => $field:ident: $field_type:tt = $default:expr),* $(,)?
+    }) => {
+        $(
+            #[allow(unused_mut)]
+            let mut $field = $default;
+        )*
+        if $cursor.options.version != ProtocolVersion::V5 {
+            // Packets before MQTT 5 don't have properties
+        } else if $cursor.index < $cursor.buffer.len() {
+            let properties_remaining_length = VariableByteInteger::read($cursor)?;
+            let properties_start_index = $cursor.index;
+            let mut seen = 0u64;
+            while $cursor.index - properties_start_index < properties_remaining_length.into() {
+                let property_type = PropertyType::new(u8::read($cursor)?)?;
+                // Check for duplicates
+                if property_type != PropertyType::UserProperty
+                    && !(property_type == PropertyType::SubscriptionId && $packet_name == "PublishPacket") {
+                    let bit = 1u64 << (property_type as u8);
+                    if seen & bit != 0 {
+                        return Err(ProtocolError::new_err(format!(
+                            "Duplicate value for unique property type: {:?}", property_type
+                        )));
+                    }
+                    seen |= bit;
+                }
+                match property_type {
+                    $(
+                        $property_type => {
+                            read_properties!(@read, $cursor, $field, $field_type);
+                        }
+                    )*
+                    _ => {
+                        return Err(MalformedPacketError::new_err(format!(
+                            "Invalid property type: {:?}", property_type
+                        )));
+                    }
+                }
+            }
+        // Property length is optional only for packets that cannot have a payload
+        } else if !matches!($packet_name,
+            "PubAckPacket" | "PubRecPacket" | "PubRelPacket" | "PubCompPacket" | "DisconnectPacket"
+        ) {
+            return Err(MalformedPacketError::new_err("Missing property length"));
+        }
+    };
+
+    (@read, $cursor:expr, $field:ident, (Vec<$inner:ty>)) => {
+        $field.push(<$inner>::read($cursor)?)
+    };
+    (@read, $cursor:expr, $field:ident, (Option<$inner:ty>)) => {
+        $field = Some(<$inner>::read($cursor)?)
+    };
+    (@read, $cursor:expr, $field:ident, $field_type:ty) => {
+        $field = <$field_type>::read($cursor)?
+    };
+}
+
+macro_rules! write_properties {
+    ($cursor:expr, $context:expr, {
+        $($property_type:path => $field:ident: $field_type:tt = $default:expr),* $(,)?
+    }) => {
+        $(
+            write_properties!(@write, $cursor, $context, $property_type, $field, $field_type, $default);
+        )*
+    };
+
+    (@write, $cursor:expr, $context:expr, $property_type:path, $field:ident, (Vec<$inner:ty>), $default:expr) => {
+        for value in &$context.$field {
+            ($property_type as u8).write($cursor);
+            value.write($cursor);
+        }
+    };
+    (@write, $cursor:expr, $context:expr, $property_type:path, $field:ident, (Option<$inner:ty>), $default:expr) => {
+        if $context.$field.is_some() {
+            ($property_type as u8).write($cursor);
+            $context.$field.write($cursor);
+        }
+    };
+    (@write, $cursor:expr, $context:expr, $property_type:path, $field:ident, $field_type:tt, $default:expr) => {
+        if $context.$field != $default {
+            ($property_type as u8).write($cursor);
+            $context.$field.write($cursor);
+        }
+    };
+}
+
+macro_rules! nbytes_properties {
+    ($context: expr, {
+        $($property_type:path => $field:ident: $field_type:tt = $default:expr),* $(,)?
+    }) => {
+        {
+            let mut accumulator: usize = 0;
+            $(
+                nbytes_properties!(@nbytes, $context, accumulator, $field, $field_type, $default);
+            )*
+            accumulator
+        }
+    };
+
+    (@nbytes, $context:expr, $accumulator:expr, $field:ident, (Vec<$inner:ty>), $default:expr) => {
+        for value in &$context.$field {
+            $accumulator += 0u8.nbytes() + value.nbytes();
+        }
+    };
+    (@nbytes, $context:expr, $accumulator:expr, $field:ident, (Option<$inner:ty>), $default:expr) => {
+        if $context.$field.is_some() {
+            $accumulator += 0u8.nbytes() + $context.$field.nbytes()
+        }
+    };
+    (@nbytes, $context:expr, $accumulator:expr, $field:ident, $field_type:tt, $default:expr) => {
+        if $context.$field != $default {
+            $accumulator += 0u8.nbytes() + $context.$field.nbytes()
+        }
+    };
+}
+
+/// Sizes that are needed in advance to write a packet.
+#[derive(Copy, Clone)]
+pub struct PacketSize {
+    pub properties_nbytes: usize,
+    pub remaining_length: VariableByteInteger,
+}
+
+impl PacketSize {
+    /// Number of bytes of the packet including the fixed header.
+    pub fn nbytes(&self) -> usize {
+        1 + self.remaining_length.nbytes() + usize::from(self.remaining_length)
+    }
+}
+
+/// Frames that are written as packets.
+pub trait Encodable {
+    /// Computes the sizes that are needed in advance to write the packet.
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize>;
+
+    /// Writes the packet, given its size.
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()>;
+}
+
+/// Number of bytes of the properties together with their length, which packets before MQTT 5
+/// don't have.
+fn properties_section_nbytes(
+    properties_nbytes: usize,
+    version: ProtocolVersion,
+) -> PyResult<usize> {
+    if version == ProtocolVersion::V5 {
+        Ok(VariableByteInteger::new(properties_nbytes)?.nbytes() + properties_nbytes)
+    } else if properties_nbytes > 0 {
+        Err(ProtocolError::new_err(
+            "Properties are not supported before MQTT 5",
+        ))
+    } else {
+        Ok(0)
+    }
+}
+
+/// Writes the length of the properties, which packets before MQTT 5 don't have.
+fn write_properties_length(
+    cursor: &mut WriteCursor,
+    properties_nbytes: usize,
+    version: ProtocolVersion,
+) -> PyResult<()> {
+    if version == ProtocolVersion::V5 {
+        VariableByteInteger::new(properties_nbytes)?.write(cursor);
+    }
+    Ok(())
+}
+
+/// Checks that the client id has 1 to 23 characters, which MQTT 3.1 requires.
+fn check_client_id(client_id: &str, version: ProtocolVersion) -> PyResult<()> {
+    if version == ProtocolVersion::V31 && !(1..=23).contains(&client_id.chars().count()) {
+        return Err(ProtocolError::new_err(
+            "Client id must have 1 to 23 characters in MQTT 3.1",
+        ));
+    }
+    Ok(())
+}
+
+/// Conversion of the values of a frame into the values of the corresponding Python object.
+pub trait Materialize<T> {
+    fn materialize(self, py: Python) -> PyResult<T>;
+}
+
+macro_rules! materialize_as_is {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl Materialize<$type> for $type {
+                fn materialize(self, _py: Python) -> PyResult<$type> {
+                    Ok(self)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! materialize_into_py {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl Materialize<Py<PyAny>> for $type {
+                fn materialize(self, py: Python) -> PyResult<Py<PyAny>> {
+                    Ok(self.into_pyobject(py)?.into_any().unbind())
+                }
+            }
+        )*
+    };
+}
+
+materialize_as_is!(
+    bool,
+    u16,
+    u32,
+    QoS,
+    PayloadFormatIndicator,
+    ConnAckReasonCode,
+    PubAckReasonCode,
+    PubRecReasonCode,
+    PubRelReasonCode,
+    PubCompReasonCode,
+    DisconnectReasonCode,
+    AuthReasonCode,
+    VariableByteInteger,
+);
+
+materialize_into_py!(
+    &str,
+    (&str, &str),
+    VariableByteInteger,
+    SubAckReasonCode,
+    UnsubAckReasonCode,
+);
+
+impl Materialize<Py<PyString>> for &str {
+    fn materialize(self, py: Python) -> PyResult<Py<PyString>> {
+        Ok(PyString::new(py, self).unbind())
+    }
+}
+
+impl Materialize<Py<PyBytes>> for &[u8] {
+    fn materialize(self, py: Python) -> PyResult<Py<PyBytes>> {
+        Ok(PyBytes::new(py, self).unbind())
+    }
+}
+
+impl<T, U> Materialize<Option<U>> for Option<T>
+where
+    T: Materialize<U>,
+{
+    fn materialize(self, py: Python) -> PyResult<Option<U>> {
+        self.map(|value| value.materialize(py)).transpose()
+    }
+}
+
+impl<T> Materialize<Py<PyList>> for Vec<T>
+where
+    T: Materialize<Py<PyAny>>,
+{
+    fn materialize(self, py: Python) -> PyResult<Py<PyList>> {
+        let items = self
+            .into_iter()
+            .map(|item| item.materialize(py))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyList::new(py, items)?.unbind())
+    }
+}
+
+/// Conversion of the values of a Python object into the values of the corresponding frame.
+pub trait Gather<'a, T> {
+    fn gather(&'a self, py: Python<'a>) -> PyResult<T>;
+}
+
+macro_rules! gather_as_is {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl Gather<'_, $type> for $type {
+                fn gather(&self, _py: Python<'_>) -> PyResult<$type> {
+                    Ok(*self)
+                }
+            }
+        )*
+    };
+}
+
+gather_as_is!(
+    bool,
+    u16,
+    u32,
+    QoS,
+    PayloadFormatIndicator,
+    RetainHandling,
+    ConnAckReasonCode,
+    PubAckReasonCode,
+    PubRecReasonCode,
+    PubRelReasonCode,
+    PubCompReasonCode,
+    DisconnectReasonCode,
+    AuthReasonCode,
+    VariableByteInteger,
+);
+
+impl<'a> Gather<'a, &'a str> for Py<PyString> {
+    fn gather(&'a self, py: Python<'a>) -> PyResult<&'a str> {
+        self.bind(py).to_str()
+    }
+}
+
+impl<'a> Gather<'a, &'a [u8]> for Py<PyBytes> {
+    fn gather(&'a self, py: Python<'a>) -> PyResult<&'a [u8]> {
+        Ok(self.bind(py).as_bytes())
+    }
+}
+
+impl<'a, T, U> Gather<'a, Option<U>> for Option<T>
+where
+    T: Gather<'a, U>,
+{
+    fn gather(&'a self, py: Python<'a>) -> PyResult<Option<U>> {
+        self.as_ref().map(|value| value.gather(py)).transpose()
+    }
+}
+
+pub struct WillFrame<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub qos: QoS,
+    pub retain: bool,
+    pub payload_format_indicator: PayloadFormatIndicator,
+    pub message_expiry_interval: Option<u32>,
+    pub content_type: Option<&'a str>,
+    pub response_topic: Option<&'a str>,
+    pub correlation_data: Option<&'a [u8]>,
+    pub will_delay_interval: u32,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl WillFrame<'_> {
+    fn properties_nbytes(&self) -> usize {
+        nbytes_properties!(self, {
+            PropertyType::PayloadFormatIndicator => payload_format_indicator: PayloadFormatIndicator = (PayloadFormatIndicator::Unspecified),
+            PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
+            PropertyType::ContentType => content_type: (Option<&str>) = None,
+            PropertyType::ResponseTopic => response_topic: (Option<&str>) = None,
+            PropertyType::CorrelationData => correlation_data: (Option<&[u8]>) = None,
+            PropertyType::WillDelayInterval => will_delay_interval: u32 = 0,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        })
+    }
+}
+
+pub struct TopicFilterFrame<'a> {
+    pub pattern: &'a str,
+    pub max_qos: QoS,
+    pub no_local: bool,
+    pub retain_as_published: bool,
+    pub retain_handling: RetainHandling,
+}
+
+/// Packet that was parsed into values that borrow from the read buffer, without the GIL.
+pub enum Frame<'a> {
+    Connect(ConnectFrame<'a>),
+    ConnAck(ConnAckFrame<'a>),
+    Publish(PublishFrame<'a>),
+    PubAck(PubAckFrame<'a>),
+    PubRec(PubRecFrame<'a>),
+    PubRel(PubRelFrame<'a>),
+    PubComp(PubCompFrame<'a>),
+    Subscribe(SubscribeFrame<'a>),
+    SubAck(SubAckFrame<'a>),
+    Unsubscribe(UnsubscribeFrame<'a>),
+    UnsubAck(UnsubAckFrame<'a>),
+    PingReq(PingReqFrame),
+    PingResp(PingRespFrame),
+    Disconnect(DisconnectFrame<'a>),
+    Auth(AuthFrame<'a>),
+}
+
+impl<'a> Frame<'a> {
+    /// Reads the packet that the cursor is bound to, starting after its fixed header.
+    pub fn read(cursor: &mut ReadCursor<'a>, header: &FixedHeader) -> PyResult<Self> {
+        let flags = header.flags;
+        #[rustfmt::skip]
+        let frame = match header.packet_type {
+            PacketType::Connect => Self::Connect(ConnectFrame::read(cursor, flags)?),
+            PacketType::ConnAck => Self::ConnAck(ConnAckFrame::read(cursor, flags)?),
+            PacketType::Publish => Self::Publish(PublishFrame::read(cursor, flags)?),
+            PacketType::PubAck => Self::PubAck(PubAckFrame::read(cursor, flags)?),
+            PacketType::PubRec => Self::PubRec(PubRecFrame::read(cursor, flags)?),
+            PacketType::PubRel => Self::PubRel(PubRelFrame::read(cursor, flags)?),
+            PacketType::PubComp => Self::PubComp(PubCompFrame::read(cursor, flags)?),
+            PacketType::Subscribe => Self::Subscribe(SubscribeFrame::read(cursor, flags)?),
+            PacketType::SubAck => Self::SubAck(SubAckFrame::read(cursor, flags)?),
+            PacketType::Unsubscribe => Self::Unsubscribe(UnsubscribeFrame::read(cursor, flags)?),
+            PacketType::UnsubAck => Self::UnsubAck(UnsubAckFrame::read(cursor, flags)?),
+            PacketType::PingReq => Self::PingReq(PingReqFrame::read(cursor, flags)?),
+            PacketType::PingResp => Self::PingResp(PingRespFrame::read(cursor, flags)?),
+            PacketType::Disconnect => Self::Disconnect(DisconnectFrame::read(cursor, flags)?),
+            PacketType::Auth => Self::Auth(AuthFrame::read(cursor, flags)?),
+        };
+        // Check if we've read enough bytes
+        if cursor.index < cursor.buffer.len() {
+            Err(MalformedPacketError::new_err("Invalid remaining length"))
+        } else {
+            Ok(frame)
+        }
+    }
+}
+
+impl Encodable for Frame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        match self {
+            Self::Connect(frame) => frame.size(version),
+            Self::ConnAck(frame) => frame.size(version),
+            Self::Publish(frame) => frame.size(version),
+            Self::PubAck(frame) => frame.size(version),
+            Self::PubRec(frame) => frame.size(version),
+            Self::PubRel(frame) => frame.size(version),
+            Self::PubComp(frame) => frame.size(version),
+            Self::Subscribe(frame) => frame.size(version),
+            Self::SubAck(frame) => frame.size(version),
+            Self::Unsubscribe(frame) => frame.size(version),
+            Self::UnsubAck(frame) => frame.size(version),
+            Self::PingReq(frame) => frame.size(version),
+            Self::PingResp(frame) => frame.size(version),
+            Self::Disconnect(frame) => frame.size(version),
+            Self::Auth(frame) => frame.size(version),
+        }
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        match self {
+            Self::Connect(frame) => frame.write_to(cursor, size, version),
+            Self::ConnAck(frame) => frame.write_to(cursor, size, version),
+            Self::Publish(frame) => frame.write_to(cursor, size, version),
+            Self::PubAck(frame) => frame.write_to(cursor, size, version),
+            Self::PubRec(frame) => frame.write_to(cursor, size, version),
+            Self::PubRel(frame) => frame.write_to(cursor, size, version),
+            Self::PubComp(frame) => frame.write_to(cursor, size, version),
+            Self::Subscribe(frame) => frame.write_to(cursor, size, version),
+            Self::SubAck(frame) => frame.write_to(cursor, size, version),
+            Self::Unsubscribe(frame) => frame.write_to(cursor, size, version),
+            Self::UnsubAck(frame) => frame.write_to(cursor, size, version),
+            Self::PingReq(frame) => frame.write_to(cursor, size, version),
+            Self::PingResp(frame) => frame.write_to(cursor, size, version),
+            Self::Disconnect(frame) => frame.write_to(cursor, size, version),
+            Self::Auth(frame) => frame.write_to(cursor, size, version),
+        }
+    }
+}
+
+pub struct ConnectFrame<'a> {
+    pub client_id: &'a str,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a [u8]>,
+    pub clean_start: bool,
+    pub will: Option<WillFrame<'a>>,
+    pub keep_alive: u16,
+    pub session_expiry_interval: u32,
+    pub authentication_method: Option<&'a str>,
+    pub authentication_data: Option<&'a [u8]>,
+    pub request_problem_info: bool,
+    pub request_response_info: bool,
+    pub receive_max: u16,
+    pub topic_alias_max: u16,
+    pub max_packet_size: Option<u32>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> ConnectFrame<'a> {
+    /// Checks the fixed header flags and reads the protocol name and level at the start of the
+    /// variable header, returning the version that they stand for.
+    pub fn read_protocol(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<ProtocolVersion> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.1.2] Variable header
+        let protocol_name = <&[u8]>::read(cursor)?;
+        let protocol_level = u8::read(cursor)?;
+        ProtocolVersion::from_protocol(protocol_name, protocol_level)
+    }
+
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        let version = Self::read_protocol(cursor, flags)?;
+        if version != cursor.options.version {
+            return Err(unsupported_protocol_version(
+                version.protocol_name().as_bytes(),
+                version as u8,
+            ));
+        }
+        let packet_flags = u8::read(cursor)?;
+        // The reserved bit must be 0, as must will QoS and will retain if the will flag is 0
+        if (packet_flags & 0x01) != 0 || (packet_flags & 0x04) == 0 && (packet_flags & 0x38) != 0 {
+            return Err(MalformedPacketError::new_err("Invalid connect flags"));
+        }
+        // Before MQTT 5, the password flag must be 0 if the username flag is 0
+        if cursor.options.version != ProtocolVersion::V5
+            && (packet_flags & 0x80) == 0
+            && (packet_flags & 0x40) != 0
+        {
+            return Err(MalformedPacketError::new_err("Invalid connect flags"));
+        }
+        let clean_start = (packet_flags & 0x02) != 0;
+        let keep_alive = u16::read(cursor)?;
+        read_properties!("ConnectPacket", cursor, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: u32 = 0,
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&'a str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&'a [u8]>) = None,
+            PropertyType::RequestProblemInfo => request_problem_info: bool = true,
+            PropertyType::RequestResponseInfo => request_response_info: bool = false,
+            PropertyType::ReceiveMax => receive_max: u16 = 65535,
+            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
+            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        // [3.1.3] Payload
+        let client_id = <&str>::read(cursor)?;
+        check_client_id(client_id, cursor.options.version)?;
+        let will = if (packet_flags & 0x04) != 0 {
+            read_properties!("Will", cursor, {
+                PropertyType::PayloadFormatIndicator => payload_format_indicator: PayloadFormatIndicator = PayloadFormatIndicator::Unspecified,
+                PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
+                PropertyType::ContentType => content_type: (Option<&'a str>) = None,
+                PropertyType::ResponseTopic => response_topic: (Option<&'a str>) = None,
+                PropertyType::CorrelationData => correlation_data: (Option<&'a [u8]>) = None,
+                PropertyType::WillDelayInterval => will_delay_interval: u32 = 0,
+                PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+            });
+            let topic = <&str>::read(cursor)?;
+            let payload = <&[u8]>::read(cursor)?;
+            if cursor.options.check_payload_format {
+                check_payload_format(payload, payload_format_indicator)?;
+            }
+            let will = WillFrame {
+                topic,
+                payload,
+                qos: QoS::new((packet_flags >> 3) & 0x03)?,
+                retain: (packet_flags & 0x20) != 0,
+                payload_format_indicator,
+                message_expiry_interval,
+                content_type,
+                response_topic,
+                correlation_data,
+                will_delay_interval,
+                user_properties,
+            };
+            Some(will)
+        } else {
+            None
+        };
+        let username = if (packet_flags & 0x80) != 0 {
+            Some(<&str>::read(cursor)?)
+        } else {
+            None
+        };
+        let password = if (packet_flags & 0x40) != 0 {
+            Some(<&[u8]>::read(cursor)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            client_id,
+            username,
+            password,
+            clean_start,
+            will,
+            keep_alive,
+            session_expiry_interval,
+            authentication_method,
+            authentication_data,
+            request_problem_info,
+            request_response_info,
+            receive_max,
+            topic_alias_max,
+            max_packet_size,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for ConnectFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: u32 = 0,
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&[u8]>) = None,
+            PropertyType::RequestProblemInfo => request_problem_info: bool = true,
+            PropertyType::RequestResponseInfo => request_response_info: bool = false,
+            PropertyType::ReceiveMax => receive_max: u16 = 65535,
+            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
+            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        check_client_id(self.client_id, version)?;
+        if version != ProtocolVersion::V5 && self.username.is_none() && self.password.is_some() {
+            return Err(ProtocolError::new_err(
+                "Password without username is not supported before MQTT 5",
+            ));
+        }
+        let will_nbytes = match &self.will {
+            Some(will) => {
+                properties_section_nbytes(will.properties_nbytes(), version)?
+                    + will.topic.nbytes()
+                    + will.payload.nbytes()
+            },
+            None => 0,
+        };
+        let nbytes = version.protocol_name().nbytes()
+            + (version as u8).nbytes()
+            + 0u8.nbytes()
+            + self.keep_alive.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?
+            + self.client_id.nbytes()
+            + will_nbytes
+            + self.username.nbytes()
+            + self.password.nbytes();
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.1.1] Fixed header
+        let first_byte = (PacketType::Connect as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.1.2] Variable header
+        version.protocol_name().write(cursor);
+        (version as u8).write(cursor);
+        let mut packet_flags = (self.clean_start as u8) << 1;
+        if let Some(ref will) = self.will {
+            packet_flags |= 0x04;
+            packet_flags |= (will.qos as u8) << 3;
+            packet_flags |= (will.retain as u8) << 5;
+        }
+        if self.password.is_some() {
+            packet_flags |= 0x40;
+        }
+        if self.username.is_some() {
+            packet_flags |= 0x80;
+        }
+        packet_flags.write(cursor);
+        self.keep_alive.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: u32 = 0,
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&[u8]>) = None,
+            PropertyType::RequestProblemInfo => request_problem_info: bool = true,
+            PropertyType::RequestResponseInfo => request_response_info: bool = false,
+            PropertyType::ReceiveMax => receive_max: u16 = 65535,
+            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
+            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        // [3.1.3] Payload
+        self.client_id.write(cursor);
+        if let Some(ref will) = self.will {
+            write_properties_length(cursor, will.properties_nbytes(), version)?;
+            write_properties!(cursor, will, {
+                PropertyType::PayloadFormatIndicator => payload_format_indicator: PayloadFormatIndicator = (PayloadFormatIndicator::Unspecified),
+                PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
+                PropertyType::ContentType => content_type: (Option<&str>) = None,
+                PropertyType::ResponseTopic => response_topic: (Option<&str>) = None,
+                PropertyType::CorrelationData => correlation_data: (Option<&[u8]>) = None,
+                PropertyType::WillDelayInterval => will_delay_interval: u32 = 0,
+                PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+            });
+            will.topic.write(cursor);
+            will.payload.write(cursor);
+        }
+        self.username.write(cursor);
+        self.password.write(cursor);
+
+        Ok(())
+    }
+}
+
+pub struct ConnAckFrame<'a> {
+    pub session_present: bool,
+    pub reason_code: ConnAckReasonCode,
+    pub session_expiry_interval: Option<u32>,
+    pub assigned_client_id: Option<&'a str>,
+    pub server_keep_alive: Option<u16>,
+    pub authentication_method: Option<&'a str>,
+    pub authentication_data: Option<&'a [u8]>,
+    pub response_info: Option<&'a str>,
+    pub server_reference: Option<&'a str>,
+    pub reason_str: Option<&'a str>,
+    pub receive_max: u16,
+    pub topic_alias_max: u16,
+    pub max_qos: QoS,
+    pub retain_available: bool,
+    pub max_packet_size: Option<u32>,
+    pub wildcard_subscription_available: bool,
+    pub subscription_id_available: bool,
+    pub shared_subscription_available: bool,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> ConnAckFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.2.2] Variable header
+        let packet_flags = u8::read(cursor)?;
+        // MQTT 3.1 reserves the whole byte, including the session present flag
+        let mask = match cursor.options.version {
+            ProtocolVersion::V31 => 0xff,
+            _ => 0xfe,
+        };
+        if (packet_flags & mask) != 0 {
+            return Err(MalformedPacketError::new_err("Invalid acknowledge flags"));
+        }
+        let session_present = (packet_flags & 0x01) != 0;
+        let reason_code = match cursor.options.version {
+            ProtocolVersion::V5 => ConnAckReasonCode::read(cursor)?,
+            _ => ConnAckReasonCode::from_return_code(u8::read(cursor)?)?,
+        };
+        read_properties!("ConnAckPacket", cursor, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
+            PropertyType::AssignedClientId => assigned_client_id: (Option<&'a str>) = None,
+            PropertyType::ServerKeepAlive => server_keep_alive: (Option<u16>) = None,
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&'a str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&'a [u8]>) = None,
+            PropertyType::ResponseInfo => response_info: (Option<&'a str>) = None,
+            PropertyType::ServerReference => server_reference: (Option<&'a str>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::ReceiveMax => receive_max: u16 = 65535,
+            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
+            PropertyType::MaxQoS => max_qos: QoS = QoS::ExactlyOnce,
+            PropertyType::RetainAvailable => retain_available: bool = true,
+            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
+            PropertyType::WildcardSubscriptionAvailable => wildcard_subscription_available: bool = true,
+            PropertyType::SubscriptionIdAvailable => subscription_id_available: bool = true,
+            PropertyType::SharedSubscriptionAvailable => shared_subscription_available: bool = true,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            session_present,
+            reason_code,
+            session_expiry_interval,
+            assigned_client_id,
+            server_keep_alive,
+            authentication_method,
+            authentication_data,
+            response_info,
+            server_reference,
+            reason_str,
+            receive_max,
+            topic_alias_max,
+            max_qos,
+            retain_available,
+            max_packet_size,
+            wildcard_subscription_available,
+            subscription_id_available,
+            shared_subscription_available,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for ConnAckFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
+            PropertyType::AssignedClientId => assigned_client_id: (Option<&str>) = None,
+            PropertyType::ServerKeepAlive => server_keep_alive: (Option<u16>) = None,
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&[u8]>) = None,
+            PropertyType::ResponseInfo => response_info: (Option<&str>) = None,
+            PropertyType::ServerReference => server_reference: (Option<&str>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::ReceiveMax => receive_max: u16 = 65535,
+            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
+            PropertyType::MaxQoS => max_qos: QoS = (QoS::ExactlyOnce),
+            PropertyType::RetainAvailable => retain_available: bool = true,
+            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
+            PropertyType::WildcardSubscriptionAvailable => wildcard_subscription_available: bool = true,
+            PropertyType::SubscriptionIdAvailable => subscription_id_available: bool = true,
+            PropertyType::SharedSubscriptionAvailable => shared_subscription_available: bool = true,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5 && self.reason_code.return_code().is_none() {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        if version == ProtocolVersion::V31 && self.session_present {
+            return Err(ProtocolError::new_err(
+                "Session present is not supported before MQTT 3.1.1",
+            ));
+        }
+        let nbytes = 0u8.nbytes()
+            + self.reason_code.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?;
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.2.1] Fixed header
+        let first_byte = (PacketType::ConnAck as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.2.2] Variable header
+        let packet_flags = self.session_present as u8;
+        packet_flags.write(cursor);
+        match version {
+            ProtocolVersion::V5 => self.reason_code.write(cursor),
+            // Reason codes without return code were rejected when computing the size
+            _ => self
+                .reason_code
+                .return_code()
+                .unwrap_or_default()
+                .write(cursor),
+        }
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
+            PropertyType::AssignedClientId => assigned_client_id: (Option<&str>) = None,
+            PropertyType::ServerKeepAlive => server_keep_alive: (Option<u16>) = None,
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&[u8]>) = None,
+            PropertyType::ResponseInfo => response_info: (Option<&str>) = None,
+            PropertyType::ServerReference => server_reference: (Option<&str>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::ReceiveMax => receive_max: u16 = 65535,
+            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
+            PropertyType::MaxQoS => max_qos: QoS = (QoS::ExactlyOnce),
+            PropertyType::RetainAvailable => retain_available: bool = true,
+            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
+            PropertyType::WildcardSubscriptionAvailable => wildcard_subscription_available: bool = true,
+            PropertyType::SubscriptionIdAvailable => subscription_id_available: bool = true,
+            PropertyType::SharedSubscriptionAvailable => shared_subscription_available: bool = true,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        Ok(())
+    }
+}
+
+pub struct PublishFrame<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    /// Index of the payload in the read buffer
+    pub payload_index: usize,
+    pub qos: QoS,
+    pub retain: bool,
+    pub packet_id: Option<u16>,
+    pub duplicate: bool,
+    pub payload_format_indicator: PayloadFormatIndicator,
+    pub message_expiry_interval: Option<u32>,
+    pub content_type: Option<&'a str>,
+    pub response_topic: Option<&'a str>,
+    pub correlation_data: Option<&'a [u8]>,
+    pub subscription_ids: Vec<VariableByteInteger>,
+    pub topic_alias: Option<u16>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> PublishFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        let retain = (flags & 0x01) != 0;
+        let qos = QoS::new((flags >> 1) & 0x03)?;
+        let duplicate = (flags & 0x08) != 0;
+
+        // [3.3.2] Variable header
+        let topic = <&str>::read(cursor)?;
+        let packet_id = if qos == QoS::AtLeastOnce || qos == QoS::ExactlyOnce {
+            Some(u16::read(cursor)?)
+        } else {
+            None
+        };
+        read_properties!("PublishPacket", cursor, {
+            PropertyType::PayloadFormatIndicator => payload_format_indicator: PayloadFormatIndicator = PayloadFormatIndicator::Unspecified,
+            PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
+            PropertyType::ContentType => content_type: (Option<&'a str>) = None,
+            PropertyType::ResponseTopic => response_topic: (Option<&'a str>) = None,
+            PropertyType::CorrelationData => correlation_data: (Option<&'a [u8]>) = None,
+            PropertyType::SubscriptionId => subscription_ids: (Vec<VariableByteInteger>) = Vec::new(),
+            PropertyType::TopicAlias => topic_alias: (Option<u16>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        // [3.3.3] Payload
+        if cursor.options.check_payload_format {
+            check_payload_format(&cursor.buffer[cursor.index..], payload_format_indicator)?;
+        }
+        let payload = &cursor.buffer[cursor.index..];
+        let payload_index = cursor.index;
+        cursor.index = cursor.buffer.len();
+
+        Ok(Self {
+            topic,
+            payload,
+            payload_index,
+            qos,
+            retain,
+            packet_id,
+            duplicate,
+            payload_format_indicator,
+            message_expiry_interval,
+            content_type,
+            response_topic,
+            correlation_data,
+            subscription_ids,
+            topic_alias,
+            user_properties,
+        })
+    }
+}
+
+impl PublishFrame<'_> {
+    /// Computes the size of the packet as if its payload had the given number of bytes.
+    pub fn size_with_payload(
+        &self,
+        payload_nbytes: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::PayloadFormatIndicator => payload_format_indicator: PayloadFormatIndicator = (PayloadFormatIndicator::Unspecified),
+            PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
+            PropertyType::ContentType => content_type: (Option<&str>) = None,
+            PropertyType::ResponseTopic => response_topic: (Option<&str>) = None,
+            PropertyType::CorrelationData => correlation_data: (Option<&[u8]>) = None,
+            PropertyType::SubscriptionId => subscription_ids: (Vec<VariableByteInteger>) = Vec::new(),
+            PropertyType::TopicAlias => topic_alias: (Option<u16>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        let nbytes = self.topic.nbytes()
+            + self.packet_id.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?
+            + payload_nbytes;
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    /// Writes the fixed header, the variable header, and the properties, but not the payload.
+    pub fn write_header_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.3.1] Fixed header
+        let first_byte = (PacketType::Publish as u8) << 4
+            | (self.duplicate as u8) << 3
+            | (self.qos as u8) << 1
+            | self.retain as u8;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.3.2] Variable header
+        self.topic.write(cursor);
+        self.packet_id.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::PayloadFormatIndicator => payload_format_indicator: PayloadFormatIndicator = (PayloadFormatIndicator::Unspecified),
+            PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
+            PropertyType::ContentType => content_type: (Option<&str>) = None,
+            PropertyType::ResponseTopic => response_topic: (Option<&str>) = None,
+            PropertyType::CorrelationData => correlation_data: (Option<&[u8]>) = None,
+            PropertyType::SubscriptionId => subscription_ids: (Vec<VariableByteInteger>) = Vec::new(),
+            PropertyType::TopicAlias => topic_alias: (Option<u16>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        Ok(())
+    }
+}
+
+impl Encodable for PublishFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        self.size_with_payload(self.payload.len(), version)
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        self.write_header_to(cursor, size, version)?;
+
+        // [3.3.3] Payload
+        cursor.write_slice(self.payload);
+
+        Ok(())
+    }
+}
+
+pub struct PubAckFrame<'a> {
+    pub packet_id: u16,
+    pub reason_code: PubAckReasonCode,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> PubAckFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.4.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        let reason_code = if cursor.options.version == ProtocolVersion::V5
+            && cursor.index < cursor.buffer.len()
+        {
+            PubAckReasonCode::read(cursor)?
+        } else {
+            PubAckReasonCode::Success
+        };
+        read_properties!("PubAckPacket", cursor, {
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            packet_id,
+            reason_code,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for PubAckFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5 && self.reason_code != PubAckReasonCode::Success {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + if self.reason_code != PubAckReasonCode::Success || properties_nbytes > 0 {
+                self.reason_code.nbytes()
+            } else {
+                0
+            }
+            + if properties_nbytes > 0 {
+                properties_section_nbytes(properties_nbytes, version)?
+            } else {
+                0
+            };
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.4.1] Fixed header
+        let first_byte = (PacketType::PubAck as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.4.2] Variable header
+        self.packet_id.write(cursor);
+        if self.reason_code != PubAckReasonCode::Success || size.properties_nbytes > 0 {
+            self.reason_code.write(cursor);
+            if size.properties_nbytes > 0 {
+                write_properties_length(cursor, size.properties_nbytes, version)?;
+                write_properties!(cursor, self, {
+                    PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+                    PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PubRecFrame<'a> {
+    pub packet_id: u16,
+    pub reason_code: PubRecReasonCode,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> PubRecFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.5.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        let reason_code = if cursor.options.version == ProtocolVersion::V5
+            && cursor.index < cursor.buffer.len()
+        {
+            PubRecReasonCode::read(cursor)?
+        } else {
+            PubRecReasonCode::Success
+        };
+        read_properties!("PubRecPacket", cursor, {
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            packet_id,
+            reason_code,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for PubRecFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5 && self.reason_code != PubRecReasonCode::Success {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + if self.reason_code != PubRecReasonCode::Success || properties_nbytes > 0 {
+                self.reason_code.nbytes()
+            } else {
+                0
+            }
+            + if properties_nbytes > 0 {
+                properties_section_nbytes(properties_nbytes, version)?
+            } else {
+                0
+            };
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.5.1] Fixed header
+        let first_byte = (PacketType::PubRec as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.5.2] Variable header
+        self.packet_id.write(cursor);
+        if self.reason_code != PubRecReasonCode::Success || size.properties_nbytes > 0 {
+            self.reason_code.write(cursor);
+            if size.properties_nbytes > 0 {
+                write_properties_length(cursor, size.properties_nbytes, version)?;
+                write_properties!(cursor, self, {
+                    PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+                    PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PubRelFrame<'a> {
+    pub packet_id: u16,
+    pub reason_code: PubRelReasonCode,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> PubRelFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x02 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.6.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        let reason_code = if cursor.options.version == ProtocolVersion::V5
+            && cursor.index < cursor.buffer.len()
+        {
+            PubRelReasonCode::read(cursor)?
+        } else {
+            PubRelReasonCode::Success
+        };
+        read_properties!("PubRelPacket", cursor, {
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            packet_id,
+            reason_code,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for PubRelFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5 && self.reason_code != PubRelReasonCode::Success {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + if self.reason_code != PubRelReasonCode::Success || properties_nbytes > 0 {
+                self.reason_code.nbytes()
+            } else {
+                0
+            }
+            + if properties_nbytes > 0 {
+                properties_section_nbytes(properties_nbytes, version)?
+            } else {
+                0
+            };
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.6.1] Fixed header
+        let first_byte = (PacketType::PubRel as u8) << 4 | 0x02;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.6.2] Variable header
+        self.packet_id.write(cursor);
+        if self.reason_code != PubRelReasonCode::Success || size.properties_nbytes > 0 {
+            self.reason_code.write(cursor);
+            if size.properties_nbytes > 0 {
+                write_properties_length(cursor, size.properties_nbytes, version)?;
+                write_properties!(cursor, self, {
+                    PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+                    PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PubCompFrame<'a> {
+    pub packet_id: u16,
+    pub reason_code: PubCompReasonCode,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> PubCompFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.7.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        let reason_code = if cursor.options.version == ProtocolVersion::V5
+            && cursor.index < cursor.buffer.len()
+        {
+            PubCompReasonCode::read(cursor)?
+        } else {
+            PubCompReasonCode::Success
+        };
+        read_properties!("PubCompPacket", cursor, {
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            packet_id,
+            reason_code,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for PubCompFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5 && self.reason_code != PubCompReasonCode::Success {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + if self.reason_code != PubCompReasonCode::Success || properties_nbytes > 0 {
+                self.reason_code.nbytes()
+            } else {
+                0
+            }
+            + if properties_nbytes > 0 {
+                properties_section_nbytes(properties_nbytes, version)?
+            } else {
+                0
+            };
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.7.1] Fixed header
+        let first_byte = (PacketType::PubComp as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.7.2] Variable header
+        self.packet_id.write(cursor);
+        if self.reason_code != PubCompReasonCode::Success || size.properties_nbytes > 0 {
+            self.reason_code.write(cursor);
+            if size.properties_nbytes > 0 {
+                write_properties_length(cursor, size.properties_nbytes, version)?;
+                write_properties!(cursor, self, {
+                    PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+                    PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SubscribeFrame<'a> {
+    pub packet_id: u16,
+    pub topic_filters: Vec<TopicFilterFrame<'a>>,
+    pub subscription_id: Option<VariableByteInteger>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> SubscribeFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x02 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.8.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        read_properties!("SubscribePacket", cursor, {
+            PropertyType::SubscriptionId => subscription_id: (Option<VariableByteInteger>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        // [3.8.3] Payload
+        let mut topic_filters = Vec::new();
+        while cursor.index < cursor.buffer.len() {
+            let pattern = <&str>::read(cursor)?;
+            let options = u8::read(cursor)?;
+            // Bits 6 and 7 are reserved, and retain handling 3 is not defined
+            if (options & 0xc0) != 0 || (options & 0x30) == 0x30 {
+                return Err(MalformedPacketError::new_err(
+                    "Invalid subscription options",
+                ));
+            }
+            // Before MQTT 5, all bits but the maximum QoS are reserved
+            if cursor.options.version != ProtocolVersion::V5 && (options & 0xfc) != 0 {
+                return Err(MalformedPacketError::new_err(
+                    "Invalid subscription options",
+                ));
+            }
+            let topic_filter = TopicFilterFrame {
+                pattern,
+                max_qos: QoS::new(options & 0x03)?,
+                no_local: (options >> 2) & 0x01 != 0,
+                // Before MQTT 5, the option takes its default value
+                retain_as_published: cursor.options.version != ProtocolVersion::V5
+                    || (options >> 3) & 0x01 != 0,
+                retain_handling: RetainHandling::new((options >> 4) & 0x03)?,
+            };
+            topic_filters.push(topic_filter);
+        }
+
+        Ok(Self {
+            packet_id,
+            topic_filters,
+            subscription_id,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for SubscribeFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::SubscriptionId => subscription_id: (Option<VariableByteInteger>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5
+            && self.topic_filters.iter().any(|topic_filter| {
+                // Options that don't exist before MQTT 5 must have their default values
+                topic_filter.no_local
+                    || !topic_filter.retain_as_published
+                    || topic_filter.retain_handling != RetainHandling::SendAlways
+            })
+        {
+            return Err(ProtocolError::new_err(
+                "Subscription options are not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?
+            + self
+                .topic_filters
+                .iter()
+                .map(|topic_filter| topic_filter.pattern.nbytes() + 1)
+                .sum::<usize>();
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.8.1] Fixed header
+        let first_byte = (PacketType::Subscribe as u8) << 4 | 0x02;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.8.2] Variable header
+        self.packet_id.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::SubscriptionId => subscription_id: (Option<VariableByteInteger>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        // [3.8.3] Payload
+        for topic_filter in &self.topic_filters {
+            topic_filter.pattern.write(cursor);
+            // Before MQTT 5, the option isn't written but must have its default value
+            let retain_as_published =
+                topic_filter.retain_as_published && version == ProtocolVersion::V5;
+            let options = topic_filter.max_qos as u8
+                | (topic_filter.no_local as u8) << 2
+                | (retain_as_published as u8) << 3
+                | (topic_filter.retain_handling as u8) << 4;
+            options.write(cursor);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SubAckFrame<'a> {
+    pub packet_id: u16,
+    pub reason_codes: Vec<SubAckReasonCode>,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> SubAckFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.9.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        read_properties!("SubAckPacket", cursor, {
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        // [3.9.3] Payload
+        let mut reason_codes = Vec::new();
+        while cursor.index < cursor.buffer.len() {
+            let reason_code = SubAckReasonCode::read(cursor)?;
+            if cursor.options.version != ProtocolVersion::V5 && !reason_code.is_return_code() {
+                return Err(MalformedPacketError::new_err(format!(
+                    "Invalid SubAck return code: {reason_code}"
+                )));
+            }
+            reason_codes.push(reason_code);
+        }
+
+        Ok(Self {
+            packet_id,
+            reason_codes,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for SubAckFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5
+            && !self
+                .reason_codes
+                .iter()
+                .all(|reason_code| reason_code.is_return_code())
+        {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?
+            + self
+                .reason_codes
+                .iter()
+                .map(|reason_code| reason_code.nbytes())
+                .sum::<usize>();
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.9.1] Fixed header
+        let first_byte = (PacketType::SubAck as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.9.2] Variable header
+        self.packet_id.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        // [3.9.3] Payload
+        for reason_code in &self.reason_codes {
+            reason_code.write(cursor);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct UnsubscribeFrame<'a> {
+    pub packet_id: u16,
+    pub patterns: Vec<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> UnsubscribeFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x02 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.10.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        read_properties!("UnsubscribePacket", cursor, {
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        // [3.9.3] Payload
+        let mut patterns = Vec::new();
+        while cursor.index < cursor.buffer.len() {
+            let pattern = <&str>::read(cursor)?;
+            patterns.push(pattern);
+        }
+
+        Ok(Self {
+            packet_id,
+            patterns,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for UnsubscribeFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        let nbytes = self.packet_id.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?
+            + self
+                .patterns
+                .iter()
+                .map(|pattern| pattern.nbytes())
+                .sum::<usize>();
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.9.1] Fixed header
+        let first_byte = (PacketType::Unsubscribe as u8) << 4 | 0x02;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.9.2] Variable header
+        self.packet_id.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        // [3.9.3] Payload
+        for pattern in &self.patterns {
+            pattern.write(cursor);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct UnsubAckFrame<'a> {
+    pub packet_id: u16,
+    pub reason_codes: Vec<UnsubAckReasonCode>,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> UnsubAckFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.11.2] Variable header
+        let packet_id = u16::read(cursor)?;
+        read_properties!("UnsubAckPacket", cursor, {
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        // [3.11.3] Payload, which packets before MQTT 5 don't have
+        let mut reason_codes = Vec::new();
+        while cursor.options.version == ProtocolVersion::V5 && cursor.index < cursor.buffer.len() {
+            let reason_code = UnsubAckReasonCode::read(cursor)?;
+            reason_codes.push(reason_code);
+        }
+
+        Ok(Self {
+            packet_id,
+            reason_codes,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for UnsubAckFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5 && !self.reason_codes.is_empty() {
+            return Err(ProtocolError::new_err(
+                "Reason codes are not supported before MQTT 5",
+            ));
+        }
+        let nbytes = self.packet_id.nbytes()
+            + properties_section_nbytes(properties_nbytes, version)?
+            + self
+                .reason_codes
+                .iter()
+                .map(|reason_code| reason_code.nbytes())
+                .sum::<usize>();
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.11.1] Fixed header
+        let first_byte = (PacketType::UnsubAck as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.11.2] Variable header
+        self.packet_id.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        // [3.11.3] Payload
+        for reason_code in &self.reason_codes {
+            reason_code.write(cursor);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PingReqFrame;
+
+impl PingReqFrame {
+    pub fn read(_cursor: &mut ReadCursor<'_>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        Ok(Self)
+    }
+}
+
+impl Encodable for PingReqFrame {
+    fn size(&self, _version: ProtocolVersion) -> PyResult<PacketSize> {
+        Ok(PacketSize {
+            properties_nbytes: 0,
+            remaining_length: VariableByteInteger::new(0)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        _version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.12.1] Fixed header
+        let first_byte = (PacketType::PingReq as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        Ok(())
+    }
+}
+
+pub struct PingRespFrame;
+
+impl PingRespFrame {
+    pub fn read(_cursor: &mut ReadCursor<'_>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        Ok(Self)
+    }
+}
+
+impl Encodable for PingRespFrame {
+    fn size(&self, _version: ProtocolVersion) -> PyResult<PacketSize> {
+        Ok(PacketSize {
+            properties_nbytes: 0,
+            remaining_length: VariableByteInteger::new(0)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        _version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.13.1] Fixed header
+        let first_byte = (PacketType::PingResp as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        Ok(())
+    }
+}
+
+pub struct DisconnectFrame<'a> {
+    pub reason_code: DisconnectReasonCode,
+    pub session_expiry_interval: Option<u32>,
+    pub server_reference: Option<&'a str>,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> DisconnectFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.14.2] Variable header
+        let reason_code = if cursor.options.version == ProtocolVersion::V5
+            && cursor.index < cursor.buffer.len()
+        {
+            DisconnectReasonCode::read(cursor)?
+        } else {
+            DisconnectReasonCode::NormalDisconnection
+        };
+        read_properties!("DisconnectPacket", cursor, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
+            PropertyType::ServerReference => server_reference: (Option<&'a str>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            reason_code,
+            session_expiry_interval,
+            server_reference,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for DisconnectFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
+            PropertyType::ServerReference => server_reference: (Option<&str>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        if version != ProtocolVersion::V5
+            && self.reason_code != DisconnectReasonCode::NormalDisconnection
+        {
+            return Err(ProtocolError::new_err(
+                "Reason code is not supported before MQTT 5",
+            ));
+        }
+        let nbytes = if self.reason_code != DisconnectReasonCode::NormalDisconnection
+            || properties_nbytes > 0
+        {
+            self.reason_code.nbytes()
+        } else {
+            0
+        } + if properties_nbytes > 0 {
+            properties_section_nbytes(properties_nbytes, version)?
+        } else {
+            0
+        };
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.14.1] Fixed header
+        let first_byte = (PacketType::Disconnect as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.14.2] Variable header
+        if self.reason_code != DisconnectReasonCode::NormalDisconnection
+            || size.properties_nbytes > 0
+        {
+            self.reason_code.write(cursor);
+            if size.properties_nbytes > 0 {
+                write_properties_length(cursor, size.properties_nbytes, version)?;
+                write_properties!(cursor, self, {
+                    PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
+                    PropertyType::ServerReference => server_reference: (Option<&str>) = None,
+                    PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+                    PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct AuthFrame<'a> {
+    pub reason_code: AuthReasonCode,
+    pub authentication_method: Option<&'a str>,
+    pub authentication_data: Option<&'a [u8]>,
+    pub reason_str: Option<&'a str>,
+    pub user_properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> AuthFrame<'a> {
+    pub fn read(cursor: &mut ReadCursor<'a>, flags: u8) -> PyResult<Self> {
+        // The packet type is reserved before MQTT 5
+        if cursor.options.version != ProtocolVersion::V5 {
+            return Err(MalformedPacketError::new_err(
+                "Auth packet is not supported before MQTT 5",
+            ));
+        }
+        if flags != 0x00 {
+            return Err(MalformedPacketError::new_err("Invalid fixed header flags"));
+        }
+
+        // [3.15.2] Variable header
+        let reason_code = if cursor.index < cursor.buffer.len() {
+            AuthReasonCode::read(cursor)?
+        } else {
+            AuthReasonCode::Success
+        };
+        read_properties!("AuthPacket", cursor, {
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&'a str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&'a [u8]>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&'a str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&'a str, &'a str)>) = Vec::new(),
+        });
+
+        Ok(Self {
+            reason_code,
+            authentication_method,
+            authentication_data,
+            reason_str,
+            user_properties,
+        })
+    }
+}
+
+impl Encodable for AuthFrame<'_> {
+    fn size(&self, version: ProtocolVersion) -> PyResult<PacketSize> {
+        if version != ProtocolVersion::V5 {
+            return Err(ProtocolError::new_err(
+                "Auth packet is not supported before MQTT 5",
+            ));
+        }
+        let properties_nbytes = nbytes_properties!(self, {
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&[u8]>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+        let nbytes =
+            self.reason_code.nbytes() + properties_section_nbytes(properties_nbytes, version)?;
+        Ok(PacketSize {
+            properties_nbytes,
+            remaining_length: VariableByteInteger::new(nbytes)?,
+        })
+    }
+
+    fn write_to(
+        &self,
+        cursor: &mut WriteCursor,
+        size: PacketSize,
+        version: ProtocolVersion,
+    ) -> PyResult<()> {
+        // [3.15.1] Fixed header
+        let first_byte = (PacketType::Auth as u8) << 4;
+        first_byte.write(cursor);
+        size.remaining_length.write(cursor);
+
+        // [3.15.2] Variable header
+        self.reason_code.write(cursor);
+        write_properties_length(cursor, size.properties_nbytes, version)?;
+        write_properties!(cursor, self, {
+            PropertyType::AuthenticationMethod => authentication_method: (Option<&str>) = None,
+            PropertyType::AuthenticationData => authentication_data: (Option<&[u8]>) = None,
+            PropertyType::ReasonStr => reason_str: (Option<&str>) = None,
+            PropertyType::UserProperty => user_properties: (Vec<(&str, &str)>) = Vec::new(),
+        });
+
+        Ok(())
+    }
+}
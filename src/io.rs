@@ -1,10 +1,36 @@
+use crate::check_format::check_string_format;
+use crate::check_size::check_packet_size;
+use crate::enums::{PacketType, ProtocolVersion};
+use crate::errors::{IncompleteError, MalformedPacketError, PacketTooLargeError, ProtocolError};
+use crate::topic_cache::TopicCache;
 use core::str;
 use pyo3::PyResult;
-use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyBufferError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyBytes, PyString, PyStringMethods, PyTuple};
 use std::cmp;
 use std::fmt;
+use std::slice;
+
+/// Returns the contents of a contiguous buffer as a byte slice.
+pub fn buffer_bytes(buffer: &PyBuffer<u8>) -> PyResult<&[u8]> {
+    if !buffer.is_c_contiguous() {
+        return Err(PyBufferError::new_err("Buffer must be contiguous"));
+    }
+    Ok(unsafe { slice::from_raw_parts(buffer.buf_ptr() as *const u8, buffer.len_bytes()) })
+}
+
+/// Returns the contents of a contiguous, writable buffer as a mutable byte slice.
+#[allow(clippy::mut_from_ref)]
+pub fn buffer_bytes_mut(buffer: &PyBuffer<u8>) -> PyResult<&mut [u8]> {
+    if buffer.readonly() {
+        return Err(PyBufferError::new_err("Buffer must be writable"));
+    }
+    if !buffer.is_c_contiguous() {
+        return Err(PyBufferError::new_err("Buffer must be contiguous"));
+    }
+    Ok(unsafe { slice::from_raw_parts_mut(buffer.buf_ptr() as *mut u8, buffer.len_bytes()) })
+}
 
 pub struct WriteCursor<'a> {
     pub buffer: &'a mut [u8],
@@ -15,11 +41,54 @@ impl<'a> WriteCursor<'a> {
     pub fn new(buffer: &'a mut [u8], index: usize) -> Self {
         Self { buffer, index }
     }
+
+    /// Copies the bytes into the buffer as they are, without a length prefix.
+    pub fn write_slice(&mut self, bytes: &[u8]) {
+        self.buffer[self.index..self.index + bytes.len()].copy_from_slice(bytes);
+        self.index += bytes.len();
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct ReadOptions {
+    /// Reject strings with control characters or non-characters
+    pub strict_strings: bool,
+    /// Reject packets that are larger than this number of bytes
+    pub max_packet_size: Option<u32>,
+    /// Reject payloads that don't match their payload format indicator
+    pub check_payload_format: bool,
+    /// Version of the protocol that packets are read with
+    pub version: ProtocolVersion,
+}
+
+impl ReadOptions {
+    pub fn new(
+        strict_strings: bool,
+        max_packet_size: Option<u32>,
+        check_payload_format: bool,
+        version: ProtocolVersion,
+    ) -> PyResult<Self> {
+        // An invalid argument isn't a protocol violation of the peer
+        if max_packet_size == Some(0) {
+            return Err(PyValueError::new_err("Maximum packet size must be != 0"));
+        }
+        Ok(Self {
+            strict_strings,
+            max_packet_size,
+            check_payload_format,
+            version,
+        })
+    }
 }
 
 pub struct ReadCursor<'a> {
     pub buffer: &'a [u8],
     pub index: usize,
+    pub options: ReadOptions,
+    /// Byte view of the buffer that payloads are sliced from instead of copied
+    pub source: Option<&'a Py<PyAny>>,
+    /// Cache that topics are taken from instead of creating new string objects
+    pub topic_cache: Option<&'a TopicCache>,
     bounded: bool,
 }
 
@@ -28,6 +97,9 @@ impl<'a> ReadCursor<'a> {
         Self {
             buffer,
             index: 0,
+            options: ReadOptions::default(),
+            source: None,
+            topic_cache: None,
             bounded: false,
         }
     }
@@ -36,14 +108,22 @@ impl<'a> ReadCursor<'a> {
     pub fn require(&self, length: usize) -> PyResult<()> {
         if self.buffer.len() - self.index < length {
             return if self.bounded {
-                Err(PyValueError::new_err("Invalid remaining length"))
+                Err(MalformedPacketError::new_err("Invalid remaining length"))
             } else {
-                Err(PyIndexError::new_err("Not enough bytes"))
+                Err(IncompleteError::new_err("Not enough bytes"))
             };
         }
         Ok(())
     }
 
+    /// Reads the given number of bytes as they are, without a length prefix.
+    pub fn read_slice(&mut self, length: usize) -> PyResult<&'a [u8]> {
+        self.require(length)?;
+        let result = &self.buffer[self.index..self.index + length];
+        self.index += length;
+        Ok(result)
+    }
+
     /// Binds the cursor to the next `length` bytes.
     pub fn bind(&mut self, length: usize) -> PyResult<()> {
         self.require(length)?;
@@ -57,9 +137,14 @@ impl<'a> ReadCursor<'a> {
 pub struct VariableByteInteger(u32);
 
 impl VariableByteInteger {
-    pub fn new(value: usize) -> Self {
-        assert!(value < 1 << 28);
-        Self(value as u32)
+    /// Creates the variable byte integer that encodes the length of a packet or property section.
+    pub fn new(value: usize) -> PyResult<Self> {
+        if value >= 1 << 28 {
+            return Err(PacketTooLargeError::new_err(
+                "Remaining length must be < 2**28",
+            ));
+        }
+        Ok(Self(value as u32))
     }
 }
 
@@ -93,17 +178,44 @@ impl fmt::Display for VariableByteInteger {
     }
 }
 
-#[derive(FromPyObject, IntoPyObject)]
-pub struct UserProperty(Py<PyTuple>);
+#[derive(Copy, Clone)]
+pub struct FixedHeader {
+    pub packet_type: PacketType,
+    pub flags: u8,
+    pub remaining_length: VariableByteInteger,
+}
+
+impl FixedHeader {
+    /// Reads the fixed header, or returns `None` if the buffer doesn't hold the complete fixed
+    /// header yet.
+    pub fn peek(cursor: &mut ReadCursor<'_>) -> PyResult<Option<Self>> {
+        // The remaining length ends with the first byte that has no continuation bit
+        let buffer = &cursor.buffer[cursor.index..];
+        let complete = buffer.iter().skip(1).take(4).any(|byte| byte & 0x80 == 0);
+        if !complete && buffer.len() < 5 {
+            return Ok(None);
+        }
+        Self::read(cursor).map(Some)
+    }
 
-pub trait Readable {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self>
+    /// Checks that the packet, including the fixed header of the given size, doesn't exceed the
+    /// maximum packet size.
+    pub fn check_packet_size(&self, header_nbytes: usize, options: &ReadOptions) -> PyResult<()> {
+        check_packet_size(
+            header_nbytes + usize::from(self.remaining_length),
+            options.max_packet_size,
+        )
+    }
+}
+
+pub trait Readable<'a> {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self>
     where
         Self: Sized;
 }
 
-impl Readable for u8 {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for u8 {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         cursor.require(1)?;
         let result = cursor.buffer[cursor.index];
         cursor.index += 1;
@@ -111,8 +223,8 @@ impl Readable for u8 {
     }
 }
 
-impl Readable for u16 {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for u16 {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         cursor.require(2)?;
         let result = u16::from_be_bytes(
             cursor.buffer[cursor.index..cursor.index + 2]
@@ -124,8 +236,8 @@ impl Readable for u16 {
     }
 }
 
-impl Readable for u32 {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for u32 {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         cursor.require(4)?;
         let result = u32::from_be_bytes(
             cursor.buffer[cursor.index..cursor.index + 4]
@@ -137,21 +249,21 @@ impl Readable for u32 {
     }
 }
 
-impl Readable for bool {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for bool {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         cursor.require(1)?;
         let byte = cursor.buffer[cursor.index];
         cursor.index += 1;
         match byte {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(PyValueError::new_err("Invalid boolean")),
+            _ => Err(ProtocolError::new_err("Invalid boolean")),
         }
     }
 }
 
-impl Readable for VariableByteInteger {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for VariableByteInteger {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         let mut multiplier = 1;
         let mut result = 0;
         for _ in 0..4 {
@@ -161,61 +273,59 @@ impl Readable for VariableByteInteger {
             cursor.index += 1;
             if (cursor.buffer[cursor.index - 1] & 0x80) == 0 {
                 if multiplier > 1 && value == 0 {
-                    return Err(PyValueError::new_err("Invalid variable byte integer"));
+                    return Err(MalformedPacketError::new_err(
+                        "Invalid variable byte integer",
+                    ));
                 }
                 return Ok(VariableByteInteger(result));
             }
             multiplier *= 128;
         }
-        Err(PyValueError::new_err("Invalid variable byte integer"))
+        Err(MalformedPacketError::new_err(
+            "Invalid variable byte integer",
+        ))
     }
 }
 
-impl Readable for Vec<u8> {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
-        let length = u16::read(cursor)? as usize;
-        cursor.require(length)?;
-        let result = cursor.buffer[cursor.index..cursor.index + length].to_vec();
-        cursor.index += length;
-        Ok(result)
+impl<'a> Readable<'a> for FixedHeader {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
+        let first_byte = u8::read(cursor)?;
+        let remaining_length = VariableByteInteger::read(cursor)?;
+        Ok(Self {
+            packet_type: PacketType::new(first_byte >> 4)?,
+            flags: first_byte & 0x0F,
+            remaining_length,
+        })
     }
 }
 
-impl Readable for Py<PyBytes> {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for &'a [u8] {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         let length = u16::read(cursor)? as usize;
         cursor.require(length)?;
-        let result = Python::attach(|py| {
-            PyBytes::new(py, &cursor.buffer[cursor.index..cursor.index + length]).unbind()
-        });
+        let result = &cursor.buffer[cursor.index..cursor.index + length];
         cursor.index += length;
         Ok(result)
     }
 }
 
-impl Readable for Py<PyString> {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+impl<'a> Readable<'a> for &'a str {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
         let length = u16::read(cursor)? as usize;
         cursor.require(length)?;
-        let result = Python::attach(|py| {
-            PyString::new(py, unsafe {
-                str::from_utf8_unchecked(&cursor.buffer[cursor.index..cursor.index + length])
-            })
-            .unbind()
-        });
+        let value = str::from_utf8(&cursor.buffer[cursor.index..cursor.index + length])
+            .map_err(|_| MalformedPacketError::new_err("Invalid UTF-8 string"))?;
+        check_string_format(value, cursor.options.strict_strings)?;
         cursor.index += length;
-        Ok(result)
+        Ok(value)
     }
 }
 
-impl Readable for UserProperty {
-    fn read(cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
-        let key = Py::<PyString>::read(cursor)?;
-        let value = Py::<PyString>::read(cursor)?;
-        Ok(Python::attach(|py| {
-            let tuple = PyTuple::new(py, [key.bind(py), value.bind(py)]).unwrap();
-            UserProperty(tuple.unbind())
-        }))
+impl<'a> Readable<'a> for (&'a str, &'a str) {
+    fn read(cursor: &mut ReadCursor<'a>) -> PyResult<Self> {
+        let key = <&str>::read(cursor)?;
+        let value = <&str>::read(cursor)?;
+        Ok((key, value))
     }
 }
 
@@ -308,17 +418,7 @@ impl Writable for &[u8] {
     }
 }
 
-impl Writable for Py<PyBytes> {
-    fn write(&self, cursor: &mut WriteCursor<'_>) {
-        Python::attach(|py| self.bind(py).write(cursor))
-    }
-
-    fn nbytes(&self) -> usize {
-        Python::attach(|py| self.bind(py).nbytes())
-    }
-}
-
-impl Writable for &Bound<'_, PyBytes> {
+impl Writable for &str {
     fn write(&self, cursor: &mut WriteCursor<'_>) {
         self.as_bytes().write(cursor);
     }
@@ -328,44 +428,14 @@ impl Writable for &Bound<'_, PyBytes> {
     }
 }
 
-impl Writable for Py<PyString> {
+impl Writable for (&str, &str) {
     fn write(&self, cursor: &mut WriteCursor<'_>) {
-        Python::attach(|py| self.bind(py).write(cursor))
+        self.0.write(cursor);
+        self.1.write(cursor);
     }
 
     fn nbytes(&self) -> usize {
-        Python::attach(|py| self.bind(py).nbytes())
-    }
-}
-
-impl Writable for &Bound<'_, PyString> {
-    fn write(&self, cursor: &mut WriteCursor<'_>) {
-        self.to_str().unwrap().as_bytes().write(cursor);
-    }
-
-    fn nbytes(&self) -> usize {
-        self.to_str().unwrap().as_bytes().nbytes()
-    }
-}
-
-impl Writable for UserProperty {
-    fn write(&self, cursor: &mut WriteCursor<'_>) {
-        Python::attach(|py| {
-            let tuple = self.0.bind(py);
-            let key: Py<PyString> = tuple.get_item(0).unwrap().extract().unwrap();
-            let value: Py<PyString> = tuple.get_item(1).unwrap().extract().unwrap();
-            key.write(cursor);
-            value.write(cursor);
-        })
-    }
-
-    fn nbytes(&self) -> usize {
-        Python::attach(|py| {
-            let tuple = self.0.bind(py);
-            let key: Py<PyString> = tuple.get_item(0).unwrap().extract().unwrap();
-            let value: Py<PyString> = tuple.get_item(1).unwrap().extract().unwrap();
-            key.nbytes() + value.nbytes()
-        })
+        self.0.nbytes() + self.1.nbytes()
     }
 }
 
@@ -1,54 +1,305 @@
+mod cache;
 mod check_format;
 mod check_size;
+mod convert;
+mod decoder;
 mod enums;
+mod errors;
+mod frame;
 mod io;
 mod packets;
 mod py_eq;
+mod sn;
+mod template;
+mod topic_cache;
 
+use cache::Cached;
+use decoder::Decoder;
 use enums::*;
-use io::{ReadCursor, Readable, VariableByteInteger};
+use errors::{ProtocolError, add_errors, locate};
+use frame::{ConnectFrame, Encodable, Frame};
+use io::{FixedHeader, ReadCursor, ReadOptions, Readable, WriteCursor, buffer_bytes};
 use packets::*;
+use pyo3::PyClass;
 use pyo3::PyResult;
 use pyo3::buffer::PyBuffer;
-use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::pyclass::boolean_struct::True;
+use pyo3::types::{PyBytes, PyList, PyMemoryView};
+use template::PublishTemplate;
+use topic_cache::TopicCache;
+
+/// Fills the write cache of a packet with the bytes that it was read from.
+fn cached<T>(py: Python, packet: Py<T>, start: usize, cursor: &ReadCursor) -> Py<PyAny>
+where
+    T: Cached + PyClass<Frozen = True> + Sync,
+{
+    // Reading without copying the payload shouldn't copy the whole packet instead
+    if cursor.source.is_none() {
+        let bytes = PyBytes::new(py, &cursor.buffer[start..]).unbind();
+        let version = cursor.options.version;
+        packet
+            .get()
+            .cache()
+            .set(py, &packet.get().lists(), &bytes, version);
+    }
+    packet.into_any()
+}
+
+/// Size from which packets are parsed without holding the GIL, when that's safe.
+///
+/// Releasing the GIL has a fixed cost that only pays off for packets that take a while to parse.
+const DETACH_THRESHOLD: usize = 4096;
+
+/// Reads the packet that the cursor is bound to, starting after its fixed header at `start`.
+///
+/// If `detach` is set, the buffer can't be mutated by other threads, such that large packets
+/// are parsed without holding the GIL.
+fn read_packet(
+    py: Python,
+    cursor: &mut ReadCursor,
+    header: &FixedHeader,
+    start: usize,
+    detach: bool,
+) -> PyResult<Py<PyAny>> {
+    let frame = if detach && usize::from(header.remaining_length) >= DETACH_THRESHOLD {
+        py.detach(|| Frame::read(cursor, header))
+    } else {
+        Frame::read(cursor, header)
+    };
+    let frame =
+        frame.map_err(|error| locate(py, error, Some(header.packet_type), Some(cursor.index)))?;
+    // Validation checks the packet as a whole once it's parsed, so there's no field to point to
+    materialize(py, frame, start, cursor)
+        .map_err(|error| locate(py, error, Some(header.packet_type), None))
+}
+
+/// Converts a frame into the corresponding packet object.
+fn materialize(py: Python, frame: Frame, start: usize, cursor: &ReadCursor) -> PyResult<Py<PyAny>> {
+    #[rustfmt::skip]
+    let packet = match frame {
+        Frame::Connect(frame) => cached(py, ConnectPacket::from_frame(py, frame)?, start, cursor),
+        Frame::ConnAck(frame) => cached(py, ConnAckPacket::from_frame(py, frame)?, start, cursor),
+        Frame::Publish(frame) => cached(py, PublishPacket::from_frame(py, frame, cursor.source, cursor.topic_cache)?, start, cursor),
+        Frame::PubAck(frame) => cached(py, PubAckPacket::from_frame(py, frame)?, start, cursor),
+        Frame::PubRec(frame) => cached(py, PubRecPacket::from_frame(py, frame)?, start, cursor),
+        Frame::PubRel(frame) => cached(py, PubRelPacket::from_frame(py, frame)?, start, cursor),
+        Frame::PubComp(frame) => cached(py, PubCompPacket::from_frame(py, frame)?, start, cursor),
+        Frame::Subscribe(frame) => cached(py, SubscribePacket::from_frame(py, frame)?, start, cursor),
+        Frame::SubAck(frame) => cached(py, SubAckPacket::from_frame(py, frame)?, start, cursor),
+        Frame::Unsubscribe(frame) => cached(py, UnsubscribePacket::from_frame(py, frame)?, start, cursor),
+        Frame::UnsubAck(frame) => cached(py, UnsubAckPacket::from_frame(py, frame)?, start, cursor),
+        Frame::PingReq(frame) => cached(py, PingReqPacket::from_frame(py, frame)?, start, cursor),
+        Frame::PingResp(frame) => cached(py, PingRespPacket::from_frame(py, frame)?, start, cursor),
+        Frame::Disconnect(frame) => cached(py, DisconnectPacket::from_frame(py, frame)?, start, cursor),
+        Frame::Auth(frame) => cached(py, AuthPacket::from_frame(py, frame)?, start, cursor),
+    };
+    Ok(packet)
+}
+
+/// Checks that the buffer is exported by a `bytes` object, whose contents can't change while the
+/// GIL is released.
+///
+/// Read-only views of mutable objects like `bytearray` or `mmap` don't qualify, as other threads
+/// can still write to the underlying object.
+fn is_immutable(py: Python, buffer: &PyBuffer<u8>) -> bool {
+    buffer.obj(py).is_some_and(|obj| {
+        obj.is_instance_of::<PyBytes>()
+            || obj.cast::<PyMemoryView>().is_ok_and(|view| {
+                view.getattr("obj")
+                    .is_ok_and(|base| base.is_instance_of::<PyBytes>())
+            })
+    })
+}
+
+/// Returns the byte view that payloads are sliced from if they shouldn't be copied.
+fn payload_source(
+    py: Python,
+    buffer: &PyBuffer<u8>,
+    copy_payload: bool,
+) -> PyResult<Option<Py<PyAny>>> {
+    // Flatten the buffer so that payload slices line up with the cursor index
+    match buffer.obj(py) {
+        Some(obj) if !copy_payload => Ok(Some(
+            PyMemoryView::from(obj)?
+                .call_method1("cast", ("B",))?
+                .unbind(),
+        )),
+        _ => Ok(None),
+    }
+}
 
 #[pyfunction]
-fn read(py: Python, buffer: PyBuffer<u8>) -> PyResult<(Py<PyAny>, usize)> {
+#[pyo3(signature = (
+    buffer,
+    *,
+    strict_strings=false,
+    copy_payload=true,
+    max_packet_size=None,
+    check_payload_format=false,
+    topic_cache=None,
+    version=ProtocolVersion::V5,
+))]
+fn read(
+    py: Python,
+    buffer: PyBuffer<u8>,
+    strict_strings: bool,
+    copy_payload: bool,
+    max_packet_size: Option<u32>,
+    check_payload_format: bool,
+    topic_cache: Option<Py<TopicCache>>,
+    version: ProtocolVersion,
+) -> PyResult<(Py<PyAny>, usize)> {
+    let options = ReadOptions::new(
+        strict_strings,
+        max_packet_size,
+        check_payload_format,
+        version,
+    )?;
+    let source = payload_source(py, &buffer, copy_payload)?;
     // Parse the fixed header
-    let mut cursor = ReadCursor::new(unsafe {
-        std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, buffer.len_bytes())
-    });
-    let first_byte = u8::read(&mut cursor)?;
-    let flags = first_byte & 0x0F;
-    let remaining_length = VariableByteInteger::read(&mut cursor)?;
+    let mut cursor = ReadCursor::new(buffer_bytes(&buffer)?);
+    cursor.options = options;
+    cursor.source = source.as_ref();
+    cursor.topic_cache = topic_cache.as_ref().map(Py::get);
+    let header = FixedHeader::read(&mut cursor)
+        .map_err(|error| locate(py, error, None, Some(cursor.index)))?;
     // Bind the cursor to the remaining bytes
-    cursor.bind(remaining_length.into())?;
-    // Call the read method of the corresponding packet
-    #[rustfmt::skip]
-    let packet = match PacketType::new(first_byte >> 4)? {
-        PacketType::Connect => ConnectPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::ConnAck => ConnAckPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::Publish => PublishPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::PubAck => PubAckPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::PubRec => PubRecPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::PubRel => PubRelPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::PubComp => PubCompPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::Subscribe => SubscribePacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::SubAck => SubAckPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::Unsubscribe => UnsubscribePacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::UnsubAck => UnsubAckPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::PingReq => PingReqPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::PingResp => PingRespPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::Disconnect => DisconnectPacket::read(py, &mut cursor, flags)?.into(),
-        PacketType::Auth => AuthPacket::read(py, &mut cursor, flags)?.into(),
-    };
-    // Check if we've read enough bytes
-    if cursor.index < cursor.buffer.len() {
-        Err(PyValueError::new_err("Invalid remaining length"))
-    } else {
-        Ok((packet, cursor.index))
+    header
+        .check_packet_size(cursor.index, &options)
+        .and_then(|_| cursor.bind(header.remaining_length.into()))
+        .map_err(|error| locate(py, error, Some(header.packet_type), Some(cursor.index)))?;
+    let packet = read_packet(py, &mut cursor, &header, 0, is_immutable(py, &buffer))?;
+    Ok((packet, cursor.index))
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    buffer,
+    *,
+    strict_strings=false,
+    copy_payload=true,
+    max_packet_size=None,
+    check_payload_format=false,
+    topic_cache=None,
+    version=ProtocolVersion::V5,
+))]
+fn read_many(
+    py: Python,
+    buffer: PyBuffer<u8>,
+    strict_strings: bool,
+    copy_payload: bool,
+    max_packet_size: Option<u32>,
+    check_payload_format: bool,
+    topic_cache: Option<Py<TopicCache>>,
+    version: ProtocolVersion,
+) -> PyResult<(Py<PyList>, usize)> {
+    let options = ReadOptions::new(
+        strict_strings,
+        max_packet_size,
+        check_payload_format,
+        version,
+    )?;
+    let source = payload_source(py, &buffer, copy_payload)?;
+    let bytes = buffer_bytes(&buffer)?;
+    let detach = is_immutable(py, &buffer);
+    let packets = PyList::empty(py);
+    let mut index = 0;
+    loop {
+        // Parse the fixed header of the next packet, stopping at a partial packet
+        let mut cursor = ReadCursor::new(bytes);
+        cursor.index = index;
+        cursor.options = options;
+        cursor.source = source.as_ref();
+        cursor.topic_cache = topic_cache.as_ref().map(Py::get);
+        let Some(header) = FixedHeader::peek(&mut cursor)
+            .map_err(|error| locate(py, error, None, Some(cursor.index)))?
+        else {
+            break;
+        };
+        header
+            .check_packet_size(cursor.index - index, &options)
+            .map_err(|error| locate(py, error, Some(header.packet_type), Some(cursor.index)))?;
+        if bytes.len() - cursor.index < usize::from(header.remaining_length) {
+            break;
+        }
+        cursor.bind(header.remaining_length.into())?;
+        packets.append(read_packet(py, &mut cursor, &header, index, detach)?)?;
+        index = cursor.index;
+    }
+    Ok((packets.unbind(), index))
+}
+
+#[pyfunction]
+#[pyo3(signature = (packets, *, version=ProtocolVersion::V5))]
+fn write_many(
+    py: Python,
+    packets: &Bound<'_, PyAny>,
+    version: ProtocolVersion,
+) -> PyResult<Py<PyBytes>> {
+    let packets = packets.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+    let packets = packets
+        .iter()
+        .map(PacketRef::extract)
+        .collect::<PyResult<Vec<_>>>()?;
+    let backings = packets
+        .iter()
+        .map(|packet| packet.backing(py))
+        .collect::<PyResult<Vec<_>>>()?;
+    // Compute the sizes up front to write all packets into a single allocation
+    let frames = packets
+        .iter()
+        .zip(&backings)
+        .map(|(packet, backing)| {
+            let frame = packet.frame(py, backing)?;
+            let size = frame.size(version)?;
+            Ok((frame, size))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let nbytes = frames.iter().map(|(_, size)| size.nbytes()).sum();
+    PyBytes::new_with(py, nbytes, |buffer| {
+        let mut cursor = WriteCursor::new(buffer, 0);
+        for (frame, size) in &frames {
+            frame.write_to(&mut cursor, *size, version)?;
+        }
+        Ok(())
+    })
+    .map(|bytes| bytes.unbind())
+}
+
+#[pyfunction]
+fn peek_header(py: Python, buffer: PyBuffer<u8>) -> PyResult<Option<(PacketType, u8, u32, usize)>> {
+    let mut cursor = ReadCursor::new(buffer_bytes(&buffer)?);
+    let header = FixedHeader::peek(&mut cursor)
+        .map_err(|error| locate(py, error, None, Some(cursor.index)))?;
+    Ok(header.map(|header| {
+        (
+            header.packet_type,
+            header.flags,
+            header.remaining_length.into(),
+            cursor.index,
+        )
+    }))
+}
+
+#[pyfunction]
+fn sniff_connect(py: Python, buffer: PyBuffer<u8>) -> PyResult<(&'static str, u8)> {
+    let mut cursor = ReadCursor::new(buffer_bytes(&buffer)?);
+    let header = FixedHeader::read(&mut cursor)
+        .map_err(|error| locate(py, error, None, Some(cursor.index)))?;
+    let packet_type = Some(header.packet_type);
+    if header.packet_type != PacketType::Connect {
+        let error = ProtocolError::new_err("Packet is not a connect packet");
+        return Err(locate(py, error, packet_type, Some(cursor.index)));
+    }
+    // The protocol name and level come first, so the rest of the packet may still be missing
+    let remaining_length = usize::from(header.remaining_length);
+    if cursor.buffer.len() - cursor.index >= remaining_length {
+        cursor.bind(remaining_length)?;
     }
+    let version = ConnectFrame::read_protocol(&mut cursor, header.flags)
+        .map_err(|error| locate(py, error, packet_type, Some(cursor.index)))?;
+    Ok((version.protocol_name(), version as u8))
 }
 
 #[pymodule]
@@ -64,8 +315,10 @@ fn mqtt5(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<DisconnectReasonCode>()?;
     m.add_class::<AuthReasonCode>()?;
     // Misc
+    m.add_class::<PacketType>()?;
     m.add_class::<QoS>()?;
     m.add_class::<RetainHandling>()?;
+    m.add_class::<PayloadFormatIndicator>()?;
     m.add_class::<Will>()?;
     m.add_class::<TopicFilter>()?;
     // Packets
@@ -84,7 +337,21 @@ fn mqtt5(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PingRespPacket>()?;
     m.add_class::<DisconnectPacket>()?;
     m.add_class::<AuthPacket>()?;
+    m.add_class::<PublishTemplate>()?;
+    m.add_class::<TopicCache>()?;
+    // Streaming
+    m.add_class::<Decoder>()?;
+    // Errors
+    add_errors(m)?;
     // Functions
     m.add_function(wrap_pyfunction!(read, m)?)?;
+    m.add_function(wrap_pyfunction!(read_many, m)?)?;
+    m.add_function(wrap_pyfunction!(write_many, m)?)?;
+    m.add_function(wrap_pyfunction!(peek_header, m)?)?;
+    m.add_function(wrap_pyfunction!(sniff_connect, m)?)?;
+    m.add_function(wrap_pyfunction!(convert::upgrade, m)?)?;
+    m.add_function(wrap_pyfunction!(convert::downgrade, m)?)?;
+    // MQTT-SN
+    sn::add_module(m)?;
     Ok(())
 }
@@ -1,17 +1,19 @@
+use crate::cache::{Cached, WriteCache};
 use crate::check_format::*;
 use crate::check_size::*;
 use crate::enums::*;
-use crate::io::{ReadCursor, Readable, UserProperty, VariableByteInteger, Writable, WriteCursor};
+use crate::errors::ProtocolError;
+use crate::frame::*;
+use crate::io::{VariableByteInteger, WriteCursor, buffer_bytes, buffer_bytes_mut};
 use crate::py_eq::*;
+use crate::topic_cache::TopicCache;
 use pyo3::PyResult;
-use pyo3::exceptions::PyValueError;
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyBufferError, PyTypeError};
 use pyo3::prelude::*;
-use pyo3::types::{PyBytes, PyList, PyString};
+use pyo3::types::{PyBytes, PyList, PySlice, PyString};
 
-const PROTOCOL_NAME: &[u8] = b"MQTT";
-const PROTOCOL_VERSION: u8 = 5;
-
-fn repr_field(val: &Bound<'_, PyAny>) -> String {
+pub(crate) fn repr_field(val: &Bound<'_, PyAny>) -> String {
     if let Ok(bytes) = val.cast::<PyBytes>() {
         let bytes = bytes.as_bytes();
         if bytes.len() > 8 {
@@ -24,6 +26,29 @@ fn repr_field(val: &Bound<'_, PyAny>) -> String {
     val.repr().unwrap().to_string()
 }
 
+/// Extracts a publish payload, which is kept as it is instead of being copied.
+fn extract_payload(value: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+    if !value.is_instance_of::<PyBytes>() {
+        buffer_bytes(&PyBuffer::get(value)?)?;
+    }
+    Ok(value.clone().unbind())
+}
+
+/// Extracts binary data, copying the contents of buffers that aren't bytes objects.
+pub(crate) fn extract_binary(value: &Bound<'_, PyAny>) -> PyResult<Option<Py<PyBytes>>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    if let Ok(bytes) = value.cast::<PyBytes>() {
+        return Ok(Some(bytes.clone().unbind()));
+    }
+    let buffer = PyBuffer::<u8>::get(value)?;
+    buffer.check_size(value.py())?;
+    Ok(Some(
+        PyBytes::new(value.py(), buffer_bytes(&buffer)?).unbind(),
+    ))
+}
+
 macro_rules! py_repr {
     ($slf:expr, $name:ident, $($field:ident),+ $(,)?) => {{
         let parts = [
@@ -36,139 +61,172 @@ macro_rules! py_repr {
     };
 }
 
-macro_rules! read_properties {
-    ($packet_name:literal, $cursor:expr, {
-        $($property_type:path => $field:ident: $field_type:tt = $default:expr),* $(,)?
-    }) => {
-        $(
-            #[allow(unused_mut)]
-            let mut $field = $default;
-        )*
-        if $cursor.index < $cursor.buffer.len() {
-            let properties_remaining_length = VariableByteInteger::read($cursor)?;
-            let properties_start_index = $cursor.index;
-            let mut seen = 0u64;
-            while $cursor.index - properties_start_index < properties_remaining_length.into() {
-                let property_type = PropertyType::new(u8::read($cursor)?)?;
-                // Check for duplicates
-                if property_type != PropertyType::UserProperty
-                    && !(property_type == PropertyType::SubscriptionId && $packet_name == "PublishPacket") {
-                    let bit = 1u64 << (property_type as u8);
-                    if seen & bit != 0 {
-                        return Err(PyValueError::new_err(format!(
-                            "Duplicate value for unique property type: {:?}", property_type
-                        )));
-                    }
-                    seen |= bit;
-                }
-                match property_type {
-                    $(
-                        $property_type => {
-                            read_properties!(@read, $cursor, $field, $field_type);
-                        }
-                    )*
-                    _ => {
-                        return Err(PyValueError::new_err(format!(
-                            "Invalid property type: {:?}", property_type
-                        )));
-                    }
-                }
-            }
-        // Property length is optional only for packets that cannot have a payload
-        } else if !matches!($packet_name,
-            "PubAckPacket" | "PubRecPacket" | "PubRelPacket" | "PubCompPacket" | "DisconnectPacket"
-        ) {
-            return Err(PyValueError::new_err("Missing property length"));
+pub(crate) use py_repr;
+
+macro_rules! from_frame {
+    ($py:expr, $frame:expr, { $($field:ident),* $(,)? } $(, $extra:ident: $value:expr)*) => {
+        Self {
+            $($field: $frame.$field.materialize($py)?,)*
+            $($extra: $value,)*
+            cache: WriteCache::default(),
         }
     };
+}
 
-    (@read, $cursor:expr, $field:ident, (Py<PyList<$inner:ty>>)) => {
-        $field.append(<$inner>::read($cursor)?)?
-    };
-    (@read, $cursor:expr, $field:ident, (Option<$inner:ty>)) => {
-        $field = Some(<$inner>::read($cursor)?)
-    };
-    (@read, $cursor:expr, $field:ident, $field_type:ty) => {
-        $field = <$field_type>::read($cursor)?
+macro_rules! to_frame {
+    ($frame:ident, $py:expr, $context:expr, { $($field:ident),* $(,)? } $(, $extra:ident)*) => {
+        $frame {
+            $($field: $context.$field.gather($py)?,)*
+            $($extra,)*
+        }
     };
 }
 
-macro_rules! write_properties {
-    ($cursor:expr, $context:expr, {
-        $($property_type:path => $field:ident: $field_type:tt = $default:expr),* $(,)?
-    }) => {
-        $(
-            write_properties!(@write, $cursor, $context, $property_type, $field, $field_type, $default);
-        )*
-    };
+/// Python objects that the frame of a packet borrows from while it's written.
+pub struct Backing<'py> {
+    /// Items of the lists of the packet, in the order of `Cached::lists`
+    lists: Vec<Vec<Bound<'py, PyAny>>>,
+    /// Buffer of a payload that isn't a bytes object
+    payload: Option<PyBuffer<u8>>,
+}
 
-    (@write, $cursor:expr, $context:expr, $property_type:path, $field:ident, (Py<PyList<$inner:ty>>), $default:expr) => {
-        Python::attach(|py| {
-            if !$context.$field.bind(py).is_empty() {
-                for item in $context.$field.bind(py).iter() {
-                    ($property_type as u8).write($cursor);
-                    let value: $inner = item.extract().unwrap();
-                    value.write($cursor);
-                }
-            }
-        });
-    };
-    (@write, $cursor:expr, $context:expr, $property_type:path, $field:ident, (Option<$inner:ty>), $default:expr) => {
-        if $context.$field.is_some() {
-            ($property_type as u8).write($cursor);
-            $context.$field.write($cursor);
-        }
-    };
-    (@write, $cursor:expr, $context:expr, $property_type:path, $field:ident, $field_type:tt, $default:expr) => {
-        if $context.$field != $default {
-            ($property_type as u8).write($cursor);
-            $context.$field.write($cursor);
+impl<'py> Backing<'py> {
+    pub fn new(py: Python<'py>, lists: &[&Py<PyList>]) -> Self {
+        Self {
+            lists: lists
+                .iter()
+                .map(|list| list.bind(py).iter().collect())
+                .collect(),
+            payload: None,
         }
-    };
+    }
+
+    /// Whether the payload lies in the memory of the given buffer, e.g. a view into it.
+    fn overlaps(&self, buffer: &PyBuffer<u8>) -> bool {
+        self.payload.as_ref().is_some_and(|payload| {
+            let start = payload.buf_ptr() as usize;
+            let target = buffer.buf_ptr() as usize;
+            start < target + buffer.len_bytes() && target < start + payload.len_bytes()
+        })
+    }
+
+    /// Extracts the items of the list at the given index.
+    fn extract<'a, T>(&'a self, index: usize) -> PyResult<Vec<T>>
+    where
+        T: FromPyObject<'a, 'py>,
+        T::Error: Into<PyErr>,
+    {
+        self.lists[index]
+            .iter()
+            .map(|item| item.extract().map_err(Into::into))
+            .collect()
+    }
 }
 
-macro_rules! nbytes_properties {
-    ($context: expr, {
-        $($property_type:path => $field:ident: $field_type:tt = $default:expr),* $(,)?
-    }) => {
-        {
-            let mut accumulator: usize = 0;
-            $(
-                nbytes_properties!(@nbytes, $context, accumulator, $field, $field_type, $default);
-            )*
-            accumulator
-        }
-    };
+/// Packets that are written by gathering their values into a frame first.
+pub trait Framed: Cached {
+    type Frame<'a>: Encodable
+    where
+        Self: 'a;
 
-    (@nbytes, $context:expr, $accumulator:expr, $field:ident, (Py<PyList<$inner:ty>>), $default:expr) => {
-        Python::attach(|py| {
-            if !$context.$field.bind(py).is_empty() {
-                for item in $context.$field.bind(py).iter() {
-                    let value: $inner = item.extract().unwrap();
-                    $accumulator += 0u8.nbytes() + value.nbytes();
-                }
+    fn backing<'py>(&self, py: Python<'py>) -> PyResult<Backing<'py>> {
+        Ok(Backing::new(py, &self.lists()))
+    }
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<Self::Frame<'a>>;
+}
+
+macro_rules! reduce_frame {
+    ($frame:expr, $max_packet_size:expr, $version:expr) => {
+        if let Some(max_packet_size) = $max_packet_size {
+            // The reason string and then the user properties may be dropped to fit the packet
+            if $frame.size($version)?.nbytes() > max_packet_size as usize {
+                $frame.reason_str = None;
+            }
+            if $frame.size($version)?.nbytes() > max_packet_size as usize {
+                $frame.user_properties.clear();
             }
-        });
-    };
-    (@nbytes, $context:expr, $accumulator:expr, $field:ident, (Option<$inner:ty>), $default:expr) => {
-        if $context.$field.is_some() {
-            $accumulator += 0u8.nbytes() + $context.$field.nbytes()
-        }
-    };
-    (@nbytes, $context:expr, $accumulator:expr, $field:ident, $field_type:tt, $default:expr) => {
-        if $context.$field != $default {
-            $accumulator += 0u8.nbytes() + $context.$field.nbytes()
         }
     };
 }
 
+/// Writes a packet into a new bytes object.
+fn write_packet<T: Framed>(
+    py: Python,
+    packet: &T,
+    max_packet_size: Option<u32>,
+    version: ProtocolVersion,
+) -> PyResult<Py<PyBytes>> {
+    let backing = packet.backing(py)?;
+    write_frame(py, &packet.frame(py, &backing)?, max_packet_size, version)
+}
+
+/// Writes a frame into a new bytes object.
+fn write_frame(
+    py: Python,
+    frame: &impl Encodable,
+    max_packet_size: Option<u32>,
+    version: ProtocolVersion,
+) -> PyResult<Py<PyBytes>> {
+    let size = frame.size(version)?;
+    check_packet_size(size.nbytes(), max_packet_size)?;
+    PyBytes::new_with(py, size.nbytes(), |buffer| {
+        frame.write_to(&mut WriteCursor::new(buffer, 0), size, version)
+    })
+    .map(|bytes| bytes.unbind())
+}
+
+/// Writes a packet into a writable buffer at the given offset.
+fn write_packet_into<T: Framed>(
+    py: Python,
+    packet: &T,
+    buffer: &PyBuffer<u8>,
+    offset: usize,
+    version: ProtocolVersion,
+) -> PyResult<usize> {
+    let backing = packet.backing(py)?;
+    let frame = packet.frame(py, &backing)?;
+    let size = frame.size(version)?;
+    let nbytes = size.nbytes();
+    // The buffer can't be borrowed mutably while the payload borrows the same memory
+    if backing.overlaps(buffer) {
+        let mut temporary = vec![0; nbytes];
+        frame.write_to(&mut WriteCursor::new(&mut temporary, 0), size, version)?;
+        drop(frame);
+        target_bytes(buffer, offset, nbytes)?[offset..].copy_from_slice(&temporary);
+    } else {
+        let bytes = target_bytes(buffer, offset, nbytes)?;
+        frame.write_to(&mut WriteCursor::new(bytes, offset), size, version)?;
+    }
+    Ok(nbytes)
+}
+
+/// Returns the bytes of a writable buffer up to the end of a packet written at the offset.
+fn target_bytes(buffer: &PyBuffer<u8>, offset: usize, nbytes: usize) -> PyResult<&mut [u8]> {
+    let bytes = buffer_bytes_mut(buffer)?;
+    match offset.checked_add(nbytes) {
+        Some(end) if end <= bytes.len() => Ok(&mut bytes[..end]),
+        _ => Err(PyBufferError::new_err("Buffer is too small")),
+    }
+}
+
+/// Number of bytes of a packet when written.
+fn packet_nbytes<T: Framed>(py: Python, packet: &T, version: ProtocolVersion) -> PyResult<usize> {
+    let backing = packet.backing(py)?;
+    Ok(packet.frame(py, &backing)?.size(version)?.nbytes())
+}
+
 #[pyclass(frozen, subclass, eq, get_all, from_py_object, module = "mqtt5")]
 pub struct Will {
     pub topic: Py<PyString>,
     pub payload: Option<Py<PyBytes>>,
     pub qos: QoS,
     pub retain: bool,
-    pub payload_format_indicator: u8,
+    pub payload_format_indicator: PayloadFormatIndicator,
     pub message_expiry_interval: Option<u32>,
     pub content_type: Option<Py<PyString>>,
     pub response_topic: Option<Py<PyString>>,
@@ -177,6 +235,26 @@ pub struct Will {
     pub user_properties: Py<PyList>,
 }
 
+impl Materialize<Will> for WillFrame<'_> {
+    fn materialize(self, py: Python) -> PyResult<Will> {
+        let will = Will {
+            topic: self.topic.materialize(py)?,
+            payload: Some(self.payload.materialize(py)?),
+            qos: self.qos,
+            retain: self.retain,
+            payload_format_indicator: self.payload_format_indicator,
+            message_expiry_interval: self.message_expiry_interval,
+            content_type: self.content_type.materialize(py)?,
+            response_topic: self.response_topic.materialize(py)?,
+            correlation_data: self.correlation_data.materialize(py)?,
+            will_delay_interval: self.will_delay_interval,
+            user_properties: self.user_properties.materialize(py)?,
+        };
+        will.validate(py)?;
+        Ok(will)
+    }
+}
+
 #[pymethods]
 impl Will {
     #[new]
@@ -186,7 +264,7 @@ impl Will {
         payload=None,
         qos=QoS::AtMostOnce,
         retain=false,
-        payload_format_indicator=0,
+        payload_format_indicator=PayloadFormatIndicator::Unspecified,
         message_expiry_interval=None,
         content_type=None,
         response_topic=None,
@@ -197,28 +275,18 @@ impl Will {
     pub fn new(
         py: Python<'_>,
         topic: Py<PyString>,
-        payload: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] payload: Option<Py<PyBytes>>,
         qos: QoS,
         retain: bool,
-        payload_format_indicator: u8,
+        payload_format_indicator: PayloadFormatIndicator,
         message_expiry_interval: Option<u32>,
         content_type: Option<Py<PyString>>,
         response_topic: Option<Py<PyString>>,
-        correlation_data: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] correlation_data: Option<Py<PyBytes>>,
         will_delay_interval: u32,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        topic.check_size(py)?;
-        check_topic_format(py, &topic)?;
-        payload.check_size(py)?;
-        content_type.check_size(py)?;
-        if let Some(response_topic) = &response_topic {
-            response_topic.check_size(py)?;
-            check_topic_format(py, response_topic)?;
-        }
-        correlation_data.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             topic,
             payload,
             qos,
@@ -230,7 +298,9 @@ impl Will {
             correlation_data,
             will_delay_interval,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -252,6 +322,44 @@ impl Will {
     }
 }
 
+impl Will {
+    /// Gathers the values into a frame, given the backing of the connect packet.
+    fn frame<'a>(&'a self, py: Python<'a>, backing: &'a Backing) -> PyResult<WillFrame<'a>> {
+        // A will without payload is written with an empty payload
+        let payload = self.payload.gather(py)?.unwrap_or_default();
+        let user_properties = backing.extract(1)?;
+        Ok(to_frame!(WillFrame, py, self, {
+            topic,
+            qos,
+            retain,
+            payload_format_indicator,
+            message_expiry_interval,
+            content_type,
+            response_topic,
+            correlation_data,
+            will_delay_interval,
+        }, payload, user_properties))
+    }
+
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.topic.check_size(py)?;
+        self.topic.check_format(py)?;
+        check_topic_format(py, &self.topic)?;
+        self.payload.check_size(py)?;
+        self.content_type.check_size(py)?;
+        self.content_type.check_format(py)?;
+        if let Some(response_topic) = &self.response_topic {
+            response_topic.check_size(py)?;
+            response_topic.check_format(py)?;
+            check_topic_format(py, response_topic)?;
+        }
+        self.correlation_data.check_size(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
+}
+
 impl Clone for Will {
     fn clone(&self) -> Self {
         Python::attach(|py| Self {
@@ -295,6 +403,20 @@ pub struct TopicFilter {
     pub retain_handling: RetainHandling,
 }
 
+impl Materialize<Py<PyAny>> for TopicFilterFrame<'_> {
+    fn materialize(self, py: Python) -> PyResult<Py<PyAny>> {
+        let topic_filter = TopicFilter {
+            pattern: self.pattern.materialize(py)?,
+            max_qos: self.max_qos,
+            no_local: self.no_local,
+            retain_as_published: self.retain_as_published,
+            retain_handling: self.retain_handling,
+        };
+        topic_filter.validate(py)?;
+        Ok(Py::new(py, topic_filter)?.into_any())
+    }
+}
+
 #[pymethods]
 impl TopicFilter {
     #[new]
@@ -314,15 +436,15 @@ impl TopicFilter {
         retain_as_published: bool,
         retain_handling: RetainHandling,
     ) -> PyResult<Self> {
-        pattern.check_size(py)?;
-        check_pattern_format(py, &pattern)?;
-        Ok(Self {
+        let packet = Self {
             pattern,
             max_qos,
             no_local,
             retain_as_published,
             retain_handling,
-        })
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -338,6 +460,25 @@ impl TopicFilter {
     }
 }
 
+impl TopicFilter {
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.pattern.check_size(py)?;
+        self.pattern.check_format(py)?;
+        check_pattern_format(py, &self.pattern)
+    }
+
+    fn frame<'a>(&'a self, py: Python<'a>) -> PyResult<TopicFilterFrame<'a>> {
+        Ok(to_frame!(TopicFilterFrame, py, self, {
+            pattern,
+            max_qos,
+            no_local,
+            retain_as_published,
+            retain_handling,
+        }))
+    }
+}
+
 impl PartialEq for TopicFilter {
     fn eq(&self, other: &Self) -> bool {
         self.pattern.py_eq(&other.pattern)
@@ -348,23 +489,85 @@ impl PartialEq for TopicFilter {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct ConnectPacket {
+    #[pyo3(get)]
     pub client_id: Py<PyString>,
+    #[pyo3(get)]
     pub username: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub password: Option<Py<PyBytes>>,
+    #[pyo3(get)]
     pub clean_start: bool,
+    #[pyo3(get)]
     pub will: Option<Will>,
+    #[pyo3(get)]
     pub keep_alive: u16,
+    #[pyo3(get)]
     pub session_expiry_interval: u32,
+    #[pyo3(get)]
     pub authentication_method: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub authentication_data: Option<Py<PyBytes>>,
+    #[pyo3(get)]
     pub request_problem_info: bool,
+    #[pyo3(get)]
     pub request_response_info: bool,
+    #[pyo3(get)]
     pub receive_max: u16,
+    #[pyo3(get)]
     pub topic_alias_max: u16,
+    #[pyo3(get)]
     pub max_packet_size: Option<u32>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for ConnectPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        let mut lists = vec![&self.user_properties];
+        if let Some(will) = &self.will {
+            lists.push(&will.user_properties);
+        }
+        lists
+    }
+}
+
+impl Framed for ConnectPacket {
+    type Frame<'a> = ConnectFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<ConnectFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        let will = self
+            .will
+            .as_ref()
+            .map(|will| will.frame(py, backing))
+            .transpose()?;
+        Ok(to_frame!(ConnectFrame, py, self, {
+            client_id,
+            username,
+            password,
+            clean_start,
+            keep_alive,
+            session_expiry_interval,
+            authentication_method,
+            authentication_data,
+            request_problem_info,
+            request_response_info,
+            receive_max,
+            topic_alias_max,
+            max_packet_size,
+        }, user_properties, will))
+    }
 }
 
 #[pymethods]
@@ -392,13 +595,13 @@ impl ConnectPacket {
         py: Python<'_>,
         client_id: Py<PyString>,
         username: Option<Py<PyString>>,
-        password: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] password: Option<Py<PyBytes>>,
         clean_start: bool,
         will: Option<Will>,
         keep_alive: u16,
         session_expiry_interval: u32,
         authentication_method: Option<Py<PyString>>,
-        authentication_data: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] authentication_data: Option<Py<PyBytes>>,
         request_problem_info: bool,
         request_response_info: bool,
         receive_max: u16,
@@ -406,19 +609,7 @@ impl ConnectPacket {
         max_packet_size: Option<u32>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        client_id.check_size(py)?;
-        username.check_size(py)?;
-        password.check_size(py)?;
-        authentication_method.check_size(py)?;
-        authentication_data.check_size(py)?;
-        if receive_max == 0 {
-            return Err(PyValueError::new_err("Receive maximum must be != 0"));
-        }
-        if max_packet_size == Some(0) {
-            return Err(PyValueError::new_err("Maximum packet size must be != 0"));
-        }
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             client_id,
             username,
             password,
@@ -434,115 +625,39 @@ impl ConnectPacket {
             topic_alias_max,
             max_packet_size,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::SessionExpiryInterval => session_expiry_interval: u32 = 0,
-            PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-            PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::RequestProblemInfo => request_problem_info: bool = true,
-            PropertyType::RequestResponseInfo => request_response_info: bool = false,
-            PropertyType::ReceiveMax => receive_max: u16 = 65535,
-            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
-            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let will_properties_nbytes = self
-            .will
-            .as_ref()
-            .map_or(0, |will| nbytes_properties!(will, {
-                PropertyType::PayloadFormatIndicator => payload_format_indicator: u8 = 0,
-                PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
-                PropertyType::ContentType => content_type: (Option<Py<PyString>>) = None,
-                PropertyType::ResponseTopic => response_topic: (Option<Py<PyString>>) = None,
-                PropertyType::CorrelationData => correlation_data: (Option<Py<PyBytes>>) = None,
-                PropertyType::WillDelayInterval => will_delay_interval: u32 = 0,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            }));
-        let will_properties_remaining_length = VariableByteInteger::new(will_properties_nbytes);
-        let nbytes = PROTOCOL_NAME.nbytes()
-            + PROTOCOL_VERSION.nbytes()
-            + 0u8.nbytes()
-            + self.keep_alive.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes
-            + self.client_id.nbytes()
-            + self.will.as_ref().map_or(0, |will| {
-                will_properties_remaining_length.nbytes()
-                    + will_properties_nbytes
-                    + will.topic.nbytes()
-                    + will.payload.as_ref().map_or(0u16.nbytes(), |x| x.nbytes())
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                write_packet(py, self, max_packet_size, version)
             })
-            + self.username.nbytes()
-            + self.password.nbytes();
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.1.1] Fixed header
-            let first_byte = (PacketType::Connect as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.1.2] Variable header
-            PROTOCOL_NAME.write(&mut cursor);
-            PROTOCOL_VERSION.write(&mut cursor);
-            let mut packet_flags = (self.clean_start as u8) << 1;
-            if let Some(ref will) = self.will {
-                packet_flags |= 0x04;
-                packet_flags |= (will.qos as u8) << 3;
-                packet_flags |= (will.retain as u8) << 5;
-            }
-            if self.password.is_some() {
-                packet_flags |= 0x40;
-            }
-            if self.username.is_some() {
-                packet_flags |= 0x80;
-            }
-            packet_flags.write(&mut cursor);
-            self.keep_alive.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::SessionExpiryInterval => session_expiry_interval: u32 = 0,
-                PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-                PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-                PropertyType::RequestProblemInfo => request_problem_info: bool = true,
-                PropertyType::RequestResponseInfo => request_response_info: bool = false,
-                PropertyType::ReceiveMax => receive_max: u16 = 65535,
-                PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
-                PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            // [3.1.3] Payload
-            self.client_id.write(&mut cursor);
-            if let Some(ref will) = self.will {
-                will_properties_remaining_length.write(&mut cursor);
-                write_properties!(&mut cursor, will, {
-                    PropertyType::PayloadFormatIndicator => payload_format_indicator: u8 = 0,
-                    PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
-                    PropertyType::ContentType => content_type: (Option<Py<PyString>>) = None,
-                    PropertyType::ResponseTopic => response_topic: (Option<Py<PyString>>) = None,
-                    PropertyType::CorrelationData => correlation_data: (Option<Py<PyBytes>>) = None,
-                    PropertyType::WillDelayInterval => will_delay_interval: u32 = 0,
-                    PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-                });
-                will.topic.write(&mut cursor);
-                if let Some(ref payload) = will.payload {
-                    payload.write(&mut cursor);
-                } else {
-                    0u16.write(&mut cursor);
-                }
-            }
-            self.username.write(&mut cursor);
-            self.password.write(&mut cursor);
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -569,82 +684,28 @@ impl ConnectPacket {
 }
 
 impl ConnectPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.1.2] Variable header
-        if Vec::<u8>::read(cursor)? != PROTOCOL_NAME {
-            return Err(PyValueError::new_err("Invalid protocol name"));
-        }
-        if u8::read(cursor)? != PROTOCOL_VERSION {
-            return Err(PyValueError::new_err("Invalid protocol version"));
-        }
-        let packet_flags = u8::read(cursor)?;
-        let clean_start = (packet_flags & 0x02) != 0;
-        let keep_alive = u16::read(cursor)?;
-        read_properties!("ConnectPacket", cursor, {
-            PropertyType::SessionExpiryInterval => session_expiry_interval: u32 = 0,
-            PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-            PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::RequestProblemInfo => request_problem_info: bool = true,
-            PropertyType::RequestResponseInfo => request_response_info: bool = false,
-            PropertyType::ReceiveMax => receive_max: u16 = 65535,
-            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
-            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        if receive_max == 0 {
-            return Err(PyValueError::new_err("Receive maximum must be != 0"));
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.client_id.check_size(py)?;
+        self.client_id.check_format(py)?;
+        self.username.check_size(py)?;
+        self.username.check_format(py)?;
+        self.password.check_size(py)?;
+        self.authentication_method.check_size(py)?;
+        self.authentication_method.check_format(py)?;
+        self.authentication_data.check_size(py)?;
+        if self.receive_max == 0 {
+            return Err(ProtocolError::new_err("Receive maximum must be != 0"));
         }
-        if max_packet_size == Some(0) {
-            return Err(PyValueError::new_err("Maximum packet size must be != 0"));
+        if self.max_packet_size == Some(0) {
+            return Err(ProtocolError::new_err("Maximum packet size must be != 0"));
         }
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // [3.1.3] Payload
-        let client_id = Py::<PyString>::read(cursor)?;
-        let will = if (packet_flags & 0x04) != 0 {
-            read_properties!("Will", cursor, {
-                PropertyType::PayloadFormatIndicator => payload_format_indicator: u8 = 0,
-                PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
-                PropertyType::ContentType => content_type: (Option<Py<PyString>>) = None,
-                PropertyType::ResponseTopic => response_topic: (Option<Py<PyString>>) = None,
-                PropertyType::CorrelationData => correlation_data: (Option<Py<PyBytes>>) = None,
-                PropertyType::WillDelayInterval => will_delay_interval: u32 = 0,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-            let topic = Py::<PyString>::read(cursor)?;
-            let payload = Py::<PyBytes>::read(cursor)?;
-            Some(Will {
-                topic,
-                payload: Some(payload),
-                qos: QoS::new((packet_flags >> 3) & 0x03)?,
-                retain: (packet_flags & 0x20) != 0,
-                payload_format_indicator,
-                message_expiry_interval,
-                content_type,
-                response_topic,
-                correlation_data,
-                will_delay_interval,
-                user_properties: user_properties.unbind(),
-            })
-        } else {
-            None
-        };
-        let username = if (packet_flags & 0x80) != 0 {
-            Some(Py::<PyString>::read(cursor)?)
-        } else {
-            None
-        };
-        let password = if (packet_flags & 0x40) != 0 {
-            Some(Py::<PyBytes>::read(cursor)?)
-        } else {
-            None
-        };
-
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: ConnectFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             client_id,
             username,
             password,
@@ -659,8 +720,9 @@ impl ConnectPacket {
             receive_max,
             topic_alias_max,
             max_packet_size,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -687,27 +749,89 @@ impl PartialEq for ConnectPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct ConnAckPacket {
+    #[pyo3(get)]
     pub session_present: bool,
+    #[pyo3(get)]
     pub reason_code: ConnAckReasonCode,
+    #[pyo3(get)]
     pub session_expiry_interval: Option<u32>,
+    #[pyo3(get)]
     pub assigned_client_id: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub server_keep_alive: Option<u16>,
+    #[pyo3(get)]
     pub authentication_method: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub authentication_data: Option<Py<PyBytes>>,
+    #[pyo3(get)]
     pub response_info: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub server_reference: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub receive_max: u16,
+    #[pyo3(get)]
     pub topic_alias_max: u16,
+    #[pyo3(get)]
     pub max_qos: QoS,
+    #[pyo3(get)]
     pub retain_available: bool,
+    #[pyo3(get)]
     pub max_packet_size: Option<u32>,
+    #[pyo3(get)]
     pub wildcard_subscription_available: bool,
+    #[pyo3(get)]
     pub subscription_id_available: bool,
+    #[pyo3(get)]
     pub shared_subscription_available: bool,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for ConnAckPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for ConnAckPacket {
+    type Frame<'a> = ConnAckFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<ConnAckFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(ConnAckFrame, py, self, {
+            session_present,
+            reason_code,
+            session_expiry_interval,
+            assigned_client_id,
+            server_keep_alive,
+            authentication_method,
+            authentication_data,
+            response_info,
+            server_reference,
+            reason_str,
+            receive_max,
+            topic_alias_max,
+            max_qos,
+            retain_available,
+            max_packet_size,
+            wildcard_subscription_available,
+            subscription_id_available,
+            shared_subscription_available,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -743,7 +867,7 @@ impl ConnAckPacket {
         assigned_client_id: Option<Py<PyString>>,
         server_keep_alive: Option<u16>,
         authentication_method: Option<Py<PyString>>,
-        authentication_data: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] authentication_data: Option<Py<PyBytes>>,
         response_info: Option<Py<PyString>>,
         server_reference: Option<Py<PyString>>,
         reason_str: Option<Py<PyString>>,
@@ -757,20 +881,7 @@ impl ConnAckPacket {
         shared_subscription_available: bool,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        assigned_client_id.check_size(py)?;
-        authentication_method.check_size(py)?;
-        authentication_data.check_size(py)?;
-        response_info.check_size(py)?;
-        server_reference.check_size(py)?;
-        reason_str.check_size(py)?;
-        if receive_max == 0 {
-            return Err(PyValueError::new_err("Receive maximum must be != 0"));
-        }
-        if max_packet_size == Some(0) {
-            return Err(PyValueError::new_err("Maximum packet size must be != 0"));
-        }
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             session_present,
             reason_code,
             session_expiry_interval,
@@ -790,71 +901,42 @@ impl ConnAckPacket {
             subscription_id_available,
             shared_subscription_available,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
-            PropertyType::AssignedClientId => assigned_client_id: (Option<Py<PyString>>) = None,
-            PropertyType::ServerKeepAlive => server_keep_alive: (Option<u16>) = None,
-            PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-            PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::ResponseInfo => response_info: (Option<Py<PyString>>) = None,
-            PropertyType::ServerReference => server_reference: (Option<Py<PyString>>) = None,
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::ReceiveMax => receive_max: u16 = 65535,
-            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
-            PropertyType::MaxQoS => max_qos: QoS = (QoS::ExactlyOnce),
-            PropertyType::RetainAvailable => retain_available: bool = true,
-            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
-            PropertyType::WildcardSubscriptionAvailable => wildcard_subscription_available: bool = true,
-            PropertyType::SubscriptionIdAvailable => subscription_id_available: bool = true,
-            PropertyType::SharedSubscriptionAvailable => shared_subscription_available: bool = true,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = 0u8.nbytes()
-            + self.reason_code.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes;
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.2.1] Fixed header
-            let first_byte = (PacketType::ConnAck as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.2.2] Variable header
-            let packet_flags = self.session_present as u8;
-            packet_flags.write(&mut cursor);
-            self.reason_code.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
-                PropertyType::AssignedClientId => assigned_client_id: (Option<Py<PyString>>) = None,
-                PropertyType::ServerKeepAlive => server_keep_alive: (Option<u16>) = None,
-                PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-                PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-                PropertyType::ResponseInfo => response_info: (Option<Py<PyString>>) = None,
-                PropertyType::ServerReference => server_reference: (Option<Py<PyString>>) = None,
-                PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                PropertyType::ReceiveMax => receive_max: u16 = 65535,
-                PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
-                PropertyType::MaxQoS => max_qos: QoS = (QoS::ExactlyOnce),
-                PropertyType::RetainAvailable => retain_available: bool = true,
-                PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
-                PropertyType::WildcardSubscriptionAvailable => wildcard_subscription_available: bool = true,
-                PropertyType::SubscriptionIdAvailable => subscription_id_available: bool = true,
-                PropertyType::SharedSubscriptionAvailable => shared_subscription_available: bool = true,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
+
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -885,46 +967,31 @@ impl ConnAckPacket {
 }
 
 impl ConnAckPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.assigned_client_id.check_size(py)?;
+        self.assigned_client_id.check_format(py)?;
+        self.authentication_method.check_size(py)?;
+        self.authentication_method.check_format(py)?;
+        self.authentication_data.check_size(py)?;
+        self.response_info.check_size(py)?;
+        self.response_info.check_format(py)?;
+        self.server_reference.check_size(py)?;
+        self.server_reference.check_format(py)?;
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        if self.receive_max == 0 {
+            return Err(ProtocolError::new_err("Receive maximum must be != 0"));
         }
-
-        // [3.2.2] Variable header
-        let packet_flags = u8::read(cursor)?;
-        if (packet_flags & 0xfe) != 0 {
-            return Err(PyValueError::new_err("Invalid acknowledge flags"));
-        }
-        let session_present = (packet_flags & 0x01) != 0;
-        let reason_code = ConnAckReasonCode::read(cursor)?;
-        read_properties!("ConnAckPacket", cursor, {
-            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
-            PropertyType::AssignedClientId => assigned_client_id: (Option<Py<PyString>>) = None,
-            PropertyType::ServerKeepAlive => server_keep_alive: (Option<u16>) = None,
-            PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-            PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::ResponseInfo => response_info: (Option<Py<PyString>>) = None,
-            PropertyType::ServerReference => server_reference: (Option<Py<PyString>>) = None,
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::ReceiveMax => receive_max: u16 = 65535,
-            PropertyType::TopicAliasMax => topic_alias_max: u16 = 0,
-            PropertyType::MaxQoS => max_qos: QoS = QoS::ExactlyOnce,
-            PropertyType::RetainAvailable => retain_available: bool = true,
-            PropertyType::MaxPacketSize => max_packet_size: (Option<u32>) = None,
-            PropertyType::WildcardSubscriptionAvailable => wildcard_subscription_available: bool = true,
-            PropertyType::SubscriptionIdAvailable => subscription_id_available: bool = true,
-            PropertyType::SharedSubscriptionAvailable => shared_subscription_available: bool = true,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        if receive_max == 0 {
-            return Err(PyValueError::new_err("Receive maximum must be != 0"));
-        }
-        if max_packet_size == Some(0) {
-            return Err(PyValueError::new_err("Maximum packet size must be != 0"));
+        if self.max_packet_size == Some(0) {
+            return Err(ProtocolError::new_err("Maximum packet size must be != 0"));
         }
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: ConnAckFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             session_present,
             reason_code,
             session_expiry_interval,
@@ -943,8 +1010,9 @@ impl ConnAckPacket {
             wildcard_subscription_available,
             subscription_id_available,
             shared_subscription_available,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -975,22 +1043,88 @@ impl PartialEq for ConnAckPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct PublishPacket {
+    #[pyo3(get)]
     pub topic: Py<PyString>,
-    pub payload: Py<PyBytes>,
+    #[pyo3(get)]
+    pub payload: Py<PyAny>,
+    #[pyo3(get)]
     pub qos: QoS,
+    #[pyo3(get)]
     pub retain: bool,
+    #[pyo3(get)]
     pub packet_id: Option<u16>,
+    #[pyo3(get)]
     pub duplicate: bool,
-    pub payload_format_indicator: u8,
+    #[pyo3(get)]
+    pub payload_format_indicator: PayloadFormatIndicator,
+    #[pyo3(get)]
     pub message_expiry_interval: Option<u32>,
+    #[pyo3(get)]
     pub content_type: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub response_topic: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub correlation_data: Option<Py<PyBytes>>,
+    #[pyo3(get)]
     pub subscription_ids: Py<PyList>,
+    #[pyo3(get)]
     pub topic_alias: Option<u16>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for PublishPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.subscription_ids, &self.user_properties]
+    }
+}
+
+impl Framed for PublishPacket {
+    type Frame<'a> = PublishFrame<'a>;
+
+    fn backing<'py>(&self, py: Python<'py>) -> PyResult<Backing<'py>> {
+        let mut backing = Backing::new(py, &self.lists());
+        // Payloads read with copy_payload=False are memoryviews into the read buffer
+        if !self.payload.bind(py).is_instance_of::<PyBytes>() {
+            backing.payload = Some(PyBuffer::get(self.payload.bind(py))?);
+        }
+        Ok(backing)
+    }
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<PublishFrame<'a>> {
+        let payload = match &backing.payload {
+            Some(buffer) => buffer_bytes(buffer)?,
+            None => self.payload.bind(py).cast::<PyBytes>()?.as_bytes(),
+        };
+        // Frames that are written don't come from a read buffer
+        let payload_index = 0;
+        let subscription_ids = backing.extract(0)?;
+        let user_properties = backing.extract(1)?;
+        Ok(to_frame!(PublishFrame, py, self, {
+            topic,
+            qos,
+            retain,
+            packet_id,
+            duplicate,
+            payload_format_indicator,
+            message_expiry_interval,
+            content_type,
+            response_topic,
+            correlation_data,
+            topic_alias,
+        }, payload, payload_index, subscription_ids, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1004,7 +1138,7 @@ impl PublishPacket {
         retain=false,
         packet_id=None,
         duplicate=false,
-        payload_format_indicator=0,
+        payload_format_indicator=PayloadFormatIndicator::Unspecified,
         message_expiry_interval=None,
         content_type=None,
         response_topic=None,
@@ -1016,55 +1150,21 @@ impl PublishPacket {
     pub fn new(
         py: Python<'_>,
         topic: Py<PyString>,
-        payload: Py<PyBytes>,
+        #[pyo3(from_py_with = extract_payload)] payload: Py<PyAny>,
         qos: QoS,
         retain: bool,
         packet_id: Option<u16>,
         duplicate: bool,
-        payload_format_indicator: u8,
+        payload_format_indicator: PayloadFormatIndicator,
         message_expiry_interval: Option<u32>,
         content_type: Option<Py<PyString>>,
         response_topic: Option<Py<PyString>>,
-        correlation_data: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] correlation_data: Option<Py<PyBytes>>,
         subscription_ids: Option<Py<PyList>>,
         topic_alias: Option<u16>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        topic.check_size(py)?;
-        check_topic_format(py, &topic)?;
-        if packet_id.is_some() && qos == QoS::AtMostOnce {
-            return Err(PyValueError::new_err("Packet ID must not be set for QoS=0"));
-        }
-        if packet_id.is_none() && (qos == QoS::AtLeastOnce || qos == QoS::ExactlyOnce) {
-            return Err(PyValueError::new_err(
-                "Packet ID must be set for QoS=1 and QoS=2",
-            ));
-        }
-        content_type.check_size(py)?;
-        if let Some(response_topic) = &response_topic {
-            response_topic.check_size(py)?;
-            check_topic_format(py, response_topic)?;
-        }
-        correlation_data.check_size(py)?;
-        if let Some(subscription_ids) = &subscription_ids {
-            for item in subscription_ids.bind(py).iter() {
-                let subscription_id: VariableByteInteger = item.extract()?;
-                subscription_id.check_size(py)?;
-                if subscription_id == 0 {
-                    return Err(PyValueError::new_err("Subscription ID must be != 0"));
-                }
-            }
-        }
-        if topic_alias == Some(0) {
-            return Err(PyValueError::new_err("Topic alias must be != 0"));
-        }
-        if topic_alias.is_none() && topic.bind(py).to_str()?.is_empty() {
-            return Err(PyValueError::new_err(
-                "Topic alias must be set if topic is empty",
-            ));
-        }
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             topic,
             payload,
             qos,
@@ -1079,63 +1179,79 @@ impl PublishPacket {
             subscription_ids: subscription_ids.unwrap_or_else(|| PyList::empty(py).unbind()),
             topic_alias,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
-    }
-
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let payload = self.payload.bind(py).as_bytes();
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::PayloadFormatIndicator => payload_format_indicator: u8 = 0,
-            PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
-            PropertyType::ContentType => content_type: (Option<Py<PyString>>) = None,
-            PropertyType::ResponseTopic => response_topic: (Option<Py<PyString>>) = None,
-            PropertyType::CorrelationData => correlation_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::SubscriptionId => subscription_ids: (Py<PyList<VariableByteInteger>>) = PyList::empty(py),
-            PropertyType::TopicAlias => topic_alias: (Option<u16>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = self.topic.nbytes()
-            + self.packet_id.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes
-            + payload.len();
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.3.1] Fixed header
-            let first_byte = (PacketType::Publish as u8) << 4
-                | (self.duplicate as u8) << 3
-                | (self.qos as u8) << 1
-                | self.retain as u8;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.3.2] Variable header
-            self.topic.write(&mut cursor);
-            self.packet_id.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::PayloadFormatIndicator => payload_format_indicator: u8 = 0,
-                PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
-                PropertyType::ContentType => content_type: (Option<Py<PyString>>) = None,
-                PropertyType::ResponseTopic => response_topic: (Option<Py<PyString>>) = None,
-                PropertyType::CorrelationData => correlation_data: (Option<Py<PyBytes>>) = None,
-                PropertyType::SubscriptionId => subscription_ids: (Py<PyList<VariableByteInteger>>) = PyList::empty(py),
-                PropertyType::TopicAlias => topic_alias: (Option<u16>) = None,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            // [3.3.3] Payload
-            let payload_remaining_length = payload.len();
-            cursor.buffer[cursor.index..cursor.index + payload_remaining_length]
-                .copy_from_slice(payload);
-            cursor.index += payload_remaining_length;
-
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
+    }
+
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        // Payloads that aren't bytes are kept by reference and may change between writes
+        if self.payload.bind(py).is_instance_of::<PyBytes>() {
+            self.cache
+                .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                    write_packet(py, self, max_packet_size, version)
+                })
+        } else {
+            write_packet(py, self, max_packet_size, version)
+        }
+    }
+
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write_vectored(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyList>> {
+        let header = self.write_header(py, None, max_packet_size, version)?;
+        PyList::new(py, [header.into_any(), self.payload.clone_ref(py)]).map(|list| list.unbind())
+    }
+
+    #[pyo3(signature = (
+        payload_nbytes=None,
+        *,
+        max_packet_size=None,
+        version=ProtocolVersion::V5,
+    ))]
+    pub fn write_header(
+        &self,
+        py: Python,
+        payload_nbytes: Option<usize>,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        let backing = self.backing(py)?;
+        let frame = self.frame(py, &backing)?;
+        let payload_nbytes = payload_nbytes.unwrap_or(frame.payload.len());
+        let size = frame.size_with_payload(payload_nbytes, version)?;
+        check_packet_size(size.nbytes(), max_packet_size)?;
+        PyBytes::new_with(py, size.nbytes() - payload_nbytes, |buffer| {
+            frame.write_header_to(&mut WriteCursor::new(buffer, 0), size, version)
+        })
+        .map(|bytes| bytes.unbind())
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -1161,63 +1277,87 @@ impl PublishPacket {
 }
 
 impl PublishPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        let retain = (flags & 0x01) != 0;
-        let qos = QoS::new((flags >> 1) & 0x03)?;
-        let duplicate = (flags & 0x08) != 0;
-
-        // [3.3.2] Variable header
-        let topic = Py::<PyString>::read(cursor)?;
-        let packet_id = if qos == QoS::AtLeastOnce || qos == QoS::ExactlyOnce {
-            Some(u16::read(cursor)?)
-        } else {
-            None
-        };
-        read_properties!("PublishPacket", cursor, {
-            PropertyType::PayloadFormatIndicator => payload_format_indicator: u8 = 0,
-            PropertyType::MessageExpiryInterval => message_expiry_interval: (Option<u32>) = None,
-            PropertyType::ContentType => content_type: (Option<Py<PyString>>) = None,
-            PropertyType::ResponseTopic => response_topic: (Option<Py<PyString>>) = None,
-            PropertyType::CorrelationData => correlation_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::SubscriptionId => subscription_ids: (Py<PyList<VariableByteInteger>>) = PyList::empty(py),
-            PropertyType::TopicAlias => topic_alias: (Option<u16>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        for item in subscription_ids.iter() {
-            if item.extract::<VariableByteInteger>()? == 0 {
-                return Err(PyValueError::new_err("Subscription ID must be != 0"));
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.topic.check_size(py)?;
+        self.topic.check_format(py)?;
+        check_topic_format(py, &self.topic)?;
+        if self.packet_id.is_some() && self.qos == QoS::AtMostOnce {
+            return Err(ProtocolError::new_err(
+                "Packet ID must not be set for QoS=0",
+            ));
+        }
+        if self.packet_id.is_none()
+            && (self.qos == QoS::AtLeastOnce || self.qos == QoS::ExactlyOnce)
+        {
+            return Err(ProtocolError::new_err(
+                "Packet ID must be set for QoS=1 and QoS=2",
+            ));
+        }
+        self.content_type.check_size(py)?;
+        self.content_type.check_format(py)?;
+        if let Some(response_topic) = &self.response_topic {
+            response_topic.check_size(py)?;
+            response_topic.check_format(py)?;
+            check_topic_format(py, response_topic)?;
+        }
+        self.correlation_data.check_size(py)?;
+        for item in self.subscription_ids.bind(py).iter() {
+            let subscription_id: VariableByteInteger = item.extract()?;
+            subscription_id.check_size(py)?;
+            if subscription_id == 0 {
+                return Err(ProtocolError::new_err("Subscription ID must be != 0"));
             }
         }
-        if topic_alias == Some(0) {
-            return Err(PyValueError::new_err("Topic alias must be != 0"));
+        if self.topic_alias == Some(0) {
+            return Err(ProtocolError::new_err("Topic alias must be != 0"));
         }
-        if topic_alias.is_none() && topic.bind(py).to_str()?.is_empty() {
-            return Err(PyValueError::new_err(
+        if self.topic_alias.is_none() && self.topic.bind(py).to_str()?.is_empty() {
+            return Err(ProtocolError::new_err(
                 "Topic alias must be set if topic is empty",
             ));
         }
-
-        // [3.3.3] Payload
-        let payload = PyBytes::new(py, &cursor.buffer[cursor.index..]);
-        cursor.index = cursor.buffer.len();
-
-        // Return the Python object
-        let packet = Self {
-            topic,
-            payload: payload.unbind(),
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
+
+    pub fn from_frame(
+        py: Python,
+        frame: PublishFrame<'_>,
+        source: Option<&Py<PyAny>>,
+        topic_cache: Option<&TopicCache>,
+    ) -> PyResult<Py<Self>> {
+        // Payloads that aren't copied are sliced from the view of the read buffer
+        let payload = match source {
+            Some(source) => source.bind(py).get_item(PySlice::new(
+                py,
+                frame.payload_index as isize,
+                (frame.payload_index + frame.payload.len()) as isize,
+                1,
+            ))?,
+            None => PyBytes::new(py, frame.payload).into_any(),
+        };
+        // Topics repeat across packets, so they may be taken from the cache
+        let string = |value: &str| match topic_cache {
+            Some(topic_cache) => topic_cache.get(py, value),
+            None => PyString::new(py, value).unbind(),
+        };
+        let topic = string(frame.topic);
+        let content_type = frame.content_type.map(string);
+        let response_topic = frame.response_topic.map(string);
+        let packet = from_frame!(py, frame, {
             qos,
             retain,
             packet_id,
             duplicate,
             payload_format_indicator,
             message_expiry_interval,
-            content_type,
-            response_topic,
             correlation_data,
-            subscription_ids: subscription_ids.unbind(),
+            subscription_ids,
             topic_alias,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        }, topic: topic, payload: payload.unbind(), content_type: content_type, response_topic: response_topic);
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -1241,12 +1381,44 @@ impl PartialEq for PublishPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct PubAckPacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub reason_code: PubAckReasonCode,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for PubAckPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for PubAckPacket {
+    type Frame<'a> = PubAckFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<PubAckFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(PubAckFrame, py, self, {
+            packet_id,
+            reason_code,
+            reason_str,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1266,58 +1438,47 @@ impl PubAckPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             reason_code,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = self.packet_id.nbytes()
-            + if self.reason_code != PubAckReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.nbytes()
-            } else {
-                0
-            }
-            + if properties_nbytes > 0 {
-                properties_remaining_length.nbytes() + properties_nbytes
-            } else {
-                0
-            };
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.4.1] Fixed header
-            let first_byte = (PacketType::PubAck as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.4.2] Variable header
-            self.packet_id.write(&mut cursor);
-            if self.reason_code != PubAckReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.write(&mut cursor);
-                if properties_nbytes > 0 {
-                    properties_remaining_length.write(&mut cursor);
-                    write_properties!(&mut cursor, self, {
-                        PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                        PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-                    });
-                }
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -1333,30 +1494,22 @@ impl PubAckPacket {
 }
 
 impl PubAckPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.4.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        let reason_code = if cursor.index < cursor.buffer.len() {
-            PubAckReasonCode::read(cursor)?
-        } else {
-            PubAckReasonCode::Success
-        };
-        read_properties!("PubAckPacket", cursor, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: PubAckFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
             reason_code,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -1370,12 +1523,44 @@ impl PartialEq for PubAckPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct PubRecPacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub reason_code: PubRecReasonCode,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for PubRecPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for PubRecPacket {
+    type Frame<'a> = PubRecFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<PubRecFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(PubRecFrame, py, self, {
+            packet_id,
+            reason_code,
+            reason_str,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1395,58 +1580,47 @@ impl PubRecPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             reason_code,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = self.packet_id.nbytes()
-            + if self.reason_code != PubRecReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.nbytes()
-            } else {
-                0
-            }
-            + if properties_nbytes > 0 {
-                properties_remaining_length.nbytes() + properties_nbytes
-            } else {
-                0
-            };
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.5.1] Fixed header
-            let first_byte = (PacketType::PubRec as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.5.2] Variable header
-            self.packet_id.write(&mut cursor);
-            if self.reason_code != PubRecReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.write(&mut cursor);
-                if properties_nbytes > 0 {
-                    properties_remaining_length.write(&mut cursor);
-                    write_properties!(&mut cursor, self, {
-                        PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                        PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-                    });
-                }
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -1462,30 +1636,22 @@ impl PubRecPacket {
 }
 
 impl PubRecPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.5.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        let reason_code = if cursor.index < cursor.buffer.len() {
-            PubRecReasonCode::read(cursor)?
-        } else {
-            PubRecReasonCode::Success
-        };
-        read_properties!("PubRecPacket", cursor, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: PubRecFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
             reason_code,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -1499,12 +1665,44 @@ impl PartialEq for PubRecPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct PubRelPacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub reason_code: PubRelReasonCode,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for PubRelPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for PubRelPacket {
+    type Frame<'a> = PubRelFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<PubRelFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(PubRelFrame, py, self, {
+            packet_id,
+            reason_code,
+            reason_str,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1524,58 +1722,47 @@ impl PubRelPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             reason_code,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = self.packet_id.nbytes()
-            + if self.reason_code != PubRelReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.nbytes()
-            } else {
-                0
-            }
-            + if properties_nbytes > 0 {
-                properties_remaining_length.nbytes() + properties_nbytes
-            } else {
-                0
-            };
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.6.1] Fixed header
-            let first_byte = (PacketType::PubRel as u8) << 4 | 0x02;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.6.2] Variable header
-            self.packet_id.write(&mut cursor);
-            if self.reason_code != PubRelReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.write(&mut cursor);
-                if properties_nbytes > 0 {
-                    properties_remaining_length.write(&mut cursor);
-                    write_properties!(&mut cursor, self, {
-                        PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                        PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-                    });
-                }
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -1591,30 +1778,22 @@ impl PubRelPacket {
 }
 
 impl PubRelPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x02 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.6.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        let reason_code = if cursor.index < cursor.buffer.len() {
-            PubRelReasonCode::read(cursor)?
-        } else {
-            PubRelReasonCode::Success
-        };
-        read_properties!("PubRelPacket", cursor, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: PubRelFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
             reason_code,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -1628,12 +1807,44 @@ impl PartialEq for PubRelPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct PubCompPacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub reason_code: PubCompReasonCode,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for PubCompPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for PubCompPacket {
+    type Frame<'a> = PubCompFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<PubCompFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(PubCompFrame, py, self, {
+            packet_id,
+            reason_code,
+            reason_str,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1653,58 +1864,47 @@ impl PubCompPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             reason_code,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = self.packet_id.nbytes()
-            + if self.reason_code != PubCompReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.nbytes()
-            } else {
-                0
-            }
-            + if properties_nbytes > 0 {
-                properties_remaining_length.nbytes() + properties_nbytes
-            } else {
-                0
-            };
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.7.1] Fixed header
-            let first_byte = (PacketType::PubComp as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.7.2] Variable header
-            self.packet_id.write(&mut cursor);
-            if self.reason_code != PubCompReasonCode::Success || properties_nbytes > 0 {
-                self.reason_code.write(&mut cursor);
-                if properties_nbytes > 0 {
-                    properties_remaining_length.write(&mut cursor);
-                    write_properties!(&mut cursor, self, {
-                        PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                        PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-                    });
-                }
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -1720,30 +1920,22 @@ impl PubCompPacket {
 }
 
 impl PubCompPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.7.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        let reason_code = if cursor.index < cursor.buffer.len() {
-            PubCompReasonCode::read(cursor)?
-        } else {
-            PubCompReasonCode::Success
-        };
-        read_properties!("PubCompPacket", cursor, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: PubCompFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
             reason_code,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -1757,12 +1949,47 @@ impl PartialEq for PubCompPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct SubscribePacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub topic_filters: Py<PyList>,
+    #[pyo3(get)]
     pub subscription_id: Option<VariableByteInteger>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for SubscribePacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.topic_filters, &self.user_properties]
+    }
+}
+
+impl Framed for SubscribePacket {
+    type Frame<'a> = SubscribeFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<SubscribeFrame<'a>> {
+        let topic_filters = backing.lists[0]
+            .iter()
+            .map(|item| item.cast::<TopicFilter>()?.get().frame(py))
+            .collect::<PyResult<_>>()?;
+        let user_properties = backing.extract(1)?;
+        Ok(to_frame!(SubscribeFrame, py, self, {
+            packet_id,
+            subscription_id,
+        }, topic_filters, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1782,72 +2009,44 @@ impl SubscribePacket {
         subscription_id: Option<VariableByteInteger>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        if let Some(subscription_id) = subscription_id {
-            subscription_id.check_size(py)?;
-            if subscription_id == 0 {
-                return Err(PyValueError::new_err("Subscription ID must be != 0"));
-            }
-        }
-        if topic_filters.bind(py).is_empty() {
-            return Err(PyValueError::new_err(
-                "Topic filter list must contain at least one entry",
-            ));
-        }
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             topic_filters,
             subscription_id,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::SubscriptionId => subscription_id: (Option<VariableByteInteger>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let topic_filters = self.topic_filters.bind(py);
-        let nbytes = self.packet_id.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes
-            + topic_filters
-                .iter()
-                .try_fold(0, |acc, item| -> PyResult<usize> {
-                    Ok(acc + item.extract::<PyRef<TopicFilter>>()?.pattern.nbytes() + 1)
-                })?;
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.8.1] Fixed header
-            let first_byte = (PacketType::Subscribe as u8) << 4 | 0x02;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.8.2] Variable header
-            self.packet_id.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::SubscriptionId => subscription_id: (Option<VariableByteInteger>) = None,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            // [3.8.3] Payload
-            for item in topic_filters.iter() {
-                let topic_filter: PyRef<TopicFilter> = item.extract()?;
-                topic_filter.pattern.write(&mut cursor);
-                let options = topic_filter.max_qos as u8
-                    | (topic_filter.no_local as u8) << 2
-                    | (topic_filter.retain_as_published as u8) << 3
-                    | (topic_filter.retain_handling as u8) << 4;
-                options.write(&mut cursor);
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                write_packet(py, self, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -1863,43 +2062,31 @@ impl SubscribePacket {
 }
 
 impl SubscribePacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x02 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.8.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        read_properties!("SubscribePacket", cursor, {
-            PropertyType::SubscriptionId => subscription_id: (Option<VariableByteInteger>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        if subscription_id.is_some_and(|subscription_id| subscription_id == 0) {
-            return Err(PyValueError::new_err("Subscription ID must be != 0"));
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        if let Some(subscription_id) = self.subscription_id {
+            subscription_id.check_size(py)?;
+            if subscription_id == 0 {
+                return Err(ProtocolError::new_err("Subscription ID must be != 0"));
+            }
         }
-
-        // [3.8.3] Payload
-        let topic_filters = PyList::empty(py);
-        while cursor.index < cursor.buffer.len() {
-            let pattern = Py::<PyString>::read(cursor)?;
-            let options = u8::read(cursor)?;
-            let topic_filter = TopicFilter {
-                pattern,
-                max_qos: QoS::new(options & 0x03)?,
-                no_local: (options >> 2) & 0x01 != 0,
-                retain_as_published: (options >> 3) & 0x01 != 0,
-                retain_handling: RetainHandling::new((options >> 4) & 0x03)?,
-            };
-            topic_filters.append(topic_filter)?;
+        if self.topic_filters.bind(py).is_empty() {
+            return Err(ProtocolError::new_err(
+                "Topic filter list must contain at least one entry",
+            ));
         }
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: SubscribeFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
-            topic_filters: topic_filters.unbind(),
+            topic_filters,
             subscription_id,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -1913,12 +2100,44 @@ impl PartialEq for SubscribePacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct SubAckPacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub reason_codes: Py<PyList>,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for SubAckPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.reason_codes, &self.user_properties]
+    }
+}
+
+impl Framed for SubAckPacket {
+    type Frame<'a> = SubAckFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<SubAckFrame<'a>> {
+        let reason_codes = backing.extract(0)?;
+        let user_properties = backing.extract(1)?;
+        Ok(to_frame!(SubAckFrame, py, self, {
+            packet_id,
+            reason_str,
+        }, reason_codes, user_properties))
+    }
 }
 
 #[pymethods]
@@ -1938,57 +2157,47 @@ impl SubAckPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             reason_codes,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let reason_codes = self.reason_codes.bind(py);
-        let nbytes = self.packet_id.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes
-            + reason_codes
-                .try_iter()?
-                .try_fold(0, |acc, item| -> PyResult<usize> {
-                    Ok(acc + item?.extract::<PyRef<SubAckReasonCode>>()?.nbytes())
-                })?;
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.9.1] Fixed header
-            let first_byte = (PacketType::SubAck as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.9.2] Variable header
-            self.packet_id.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            // [3.9.3] Payload
-            for item in reason_codes.try_iter()? {
-                let reason_code: PyRef<SubAckReasonCode> = item?.extract()?;
-                reason_code.write(&mut cursor);
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -2004,32 +2213,22 @@ impl SubAckPacket {
 }
 
 impl SubAckPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.9.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        read_properties!("SubAckPacket", cursor, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-
-        // [3.9.3] Payload
-        let reason_codes = PyList::empty(py);
-        while cursor.index < cursor.buffer.len() {
-            let reason_code = SubAckReasonCode::read(cursor)?;
-            reason_codes.append(reason_code)?;
-        }
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: SubAckFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
-            reason_codes: reason_codes.unbind(),
+            reason_codes,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -2043,11 +2242,41 @@ impl PartialEq for SubAckPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct UnsubscribePacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub patterns: Py<PyList>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for UnsubscribePacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.patterns, &self.user_properties]
+    }
+}
+
+impl Framed for UnsubscribePacket {
+    type Frame<'a> = UnsubscribeFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<UnsubscribeFrame<'a>> {
+        let patterns = backing.extract(0)?;
+        let user_properties = backing.extract(1)?;
+        Ok(to_frame!(UnsubscribeFrame, py, self, {
+            packet_id,
+        }, patterns, user_properties))
+    }
 }
 
 #[pymethods]
@@ -2065,63 +2294,43 @@ impl UnsubscribePacket {
         patterns: Py<PyList>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        if patterns.bind(py).is_empty() {
-            return Err(PyValueError::new_err(
-                "Pattern list must contain at least one entry",
-            ));
-        }
-        for item in patterns.bind(py).iter() {
-            let pattern: Py<PyString> = item.extract()?;
-            pattern.check_size(py)?;
-            check_pattern_format(py, &pattern)?;
-        }
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             patterns,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let patterns = self.patterns.bind(py);
-        let nbytes = self.packet_id.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes
-            + patterns
-                .iter()
-                .try_fold(0, |acc, item| -> PyResult<usize> {
-                    Ok(acc + item.extract::<Py<PyString>>()?.nbytes())
-                })?;
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.9.1] Fixed header
-            let first_byte = (PacketType::Unsubscribe as u8) << 4 | 0x02;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.9.2] Variable header
-            self.packet_id.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            // [3.9.3] Payload
-            for item in patterns.iter() {
-                let pattern: Py<PyString> = item.extract()?;
-                pattern.write(&mut cursor);
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                write_packet(py, self, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -2130,30 +2339,30 @@ impl UnsubscribePacket {
 }
 
 impl UnsubscribePacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x02 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        if self.patterns.bind(py).is_empty() {
+            return Err(ProtocolError::new_err(
+                "Pattern list must contain at least one entry",
+            ));
         }
-
-        // [3.10.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        read_properties!("UnsubscribePacket", cursor, {
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-
-        // [3.9.3] Payload
-        let patterns = PyList::empty(py);
-        while cursor.index < cursor.buffer.len() {
-            let pattern = Py::<PyString>::read(cursor)?;
-            patterns.append(pattern)?;
+        for item in self.patterns.bind(py).iter() {
+            let pattern: Py<PyString> = item.extract()?;
+            pattern.check_size(py)?;
+            pattern.check_format(py)?;
+            check_pattern_format(py, &pattern)?;
         }
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: UnsubscribeFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
-            patterns: patterns.unbind(),
-            user_properties: user_properties.unbind(),
-        };
+            patterns,
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -2166,12 +2375,44 @@ impl PartialEq for UnsubscribePacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct UnsubAckPacket {
+    #[pyo3(get)]
     pub packet_id: u16,
+    #[pyo3(get)]
     pub reason_codes: Py<PyList>,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for UnsubAckPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.reason_codes, &self.user_properties]
+    }
+}
+
+impl Framed for UnsubAckPacket {
+    type Frame<'a> = UnsubAckFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<UnsubAckFrame<'a>> {
+        let reason_codes = backing.extract(0)?;
+        let user_properties = backing.extract(1)?;
+        Ok(to_frame!(UnsubAckFrame, py, self, {
+            packet_id,
+            reason_str,
+        }, reason_codes, user_properties))
+    }
 }
 
 #[pymethods]
@@ -2191,57 +2432,47 @@ impl UnsubAckPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             packet_id,
             reason_codes,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let reason_codes = self.reason_codes.bind(py);
-        let nbytes = self.packet_id.nbytes()
-            + properties_remaining_length.nbytes()
-            + properties_nbytes
-            + reason_codes
-                .try_iter()?
-                .try_fold(0, |acc, item| -> PyResult<usize> {
-                    Ok(acc + item?.extract::<PyRef<UnsubAckReasonCode>>()?.nbytes())
-                })?;
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.11.1] Fixed header
-            let first_byte = (PacketType::UnsubAck as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.11.2] Variable header
-            self.packet_id.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            // [3.11.3] Payload
-            for item in reason_codes.try_iter()? {
-                let reason_code: PyRef<UnsubAckReasonCode> = item?.extract()?;
-                reason_code.write(&mut cursor);
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -2257,32 +2488,22 @@ impl UnsubAckPacket {
 }
 
 impl UnsubAckPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.11.2] Variable header
-        let packet_id = u16::read(cursor)?;
-        read_properties!("UnsubAckPacket", cursor, {
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-
-        // [3.11.3] Payload
-        let reason_codes = PyList::empty(py);
-        while cursor.index < cursor.buffer.len() {
-            let reason_code = UnsubAckReasonCode::read(cursor)?;
-            reason_codes.append(reason_code)?;
-        }
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
 
-        // Return the Python object
-        let packet = Self {
+    pub fn from_frame(py: Python, frame: UnsubAckFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             packet_id,
-            reason_codes: reason_codes.unbind(),
+            reason_codes,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -2296,30 +2517,70 @@ impl PartialEq for UnsubAckPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
-pub struct PingReqPacket {}
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
+pub struct PingReqPacket {
+    cache: WriteCache,
+}
+
+impl Cached for PingReqPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![]
+    }
+}
+
+impl Framed for PingReqPacket {
+    type Frame<'a> = PingReqFrame;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        _py: Python<'py>,
+        _backing: &'a Backing<'py>,
+    ) -> PyResult<PingReqFrame> {
+        Ok(PingReqFrame)
+    }
+}
 
 #[pymethods]
 impl PingReqPacket {
     #[new]
     #[pyo3(signature = ())]
     pub fn new() -> PyResult<Self> {
-        Ok(Self {})
+        Ok(Self {
+            cache: WriteCache::default(),
+        })
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let remaining_length = VariableByteInteger::new(0);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes(), |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                write_packet(py, self, max_packet_size, version)
+            })
+    }
 
-            // [3.12.1] Fixed header
-            let first_byte = (PacketType::PingReq as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(_slf: &Bound<'_, Self>) -> String {
@@ -2328,14 +2589,13 @@ impl PingReqPacket {
 }
 
 impl PingReqPacket {
-    pub fn read(py: Python, _cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // Return the Python object
-        let packet = Self {};
-        Py::new(py, packet)
+    pub fn from_frame(py: Python, _frame: PingReqFrame) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            Self {
+                cache: WriteCache::default(),
+            },
+        )
     }
 }
 
@@ -2345,30 +2605,70 @@ impl PartialEq for PingReqPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
-pub struct PingRespPacket {}
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
+pub struct PingRespPacket {
+    cache: WriteCache,
+}
+
+impl Cached for PingRespPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![]
+    }
+}
+
+impl Framed for PingRespPacket {
+    type Frame<'a> = PingRespFrame;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        _py: Python<'py>,
+        _backing: &'a Backing<'py>,
+    ) -> PyResult<PingRespFrame> {
+        Ok(PingRespFrame)
+    }
+}
 
 #[pymethods]
 impl PingRespPacket {
     #[new]
     #[pyo3(signature = ())]
     pub fn new() -> PyResult<Self> {
-        Ok(Self {})
+        Ok(Self {
+            cache: WriteCache::default(),
+        })
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let remaining_length = VariableByteInteger::new(0);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes(), |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                write_packet(py, self, max_packet_size, version)
+            })
+    }
 
-            // [3.13.1] Fixed header
-            let first_byte = (PacketType::PingResp as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(_slf: &Bound<'_, Self>) -> String {
@@ -2377,14 +2677,13 @@ impl PingRespPacket {
 }
 
 impl PingRespPacket {
-    pub fn read(py: Python, _cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // Return the Python object
-        let packet = Self {};
-        Py::new(py, packet)
+    pub fn from_frame(py: Python, _frame: PingRespFrame) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            Self {
+                cache: WriteCache::default(),
+            },
+        )
     }
 }
 
@@ -2394,13 +2693,47 @@ impl PartialEq for PingRespPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct DisconnectPacket {
+    #[pyo3(get)]
     pub reason_code: DisconnectReasonCode,
+    #[pyo3(get)]
     pub session_expiry_interval: Option<u32>,
+    #[pyo3(get)]
     pub server_reference: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for DisconnectPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for DisconnectPacket {
+    type Frame<'a> = DisconnectFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<DisconnectFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(DisconnectFrame, py, self, {
+            reason_code,
+            session_expiry_interval,
+            server_reference,
+            reason_str,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -2422,65 +2755,48 @@ impl DisconnectPacket {
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        server_reference.check_size(py)?;
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             reason_code,
             session_expiry_interval,
             server_reference,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
-            PropertyType::ServerReference => server_reference: (Option<Py<PyString>>) = None,
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes = if self.reason_code != DisconnectReasonCode::NormalDisconnection
-            || properties_nbytes > 0
-        {
-            self.reason_code.nbytes()
-        } else {
-            0
-        } + if properties_nbytes > 0 {
-            properties_remaining_length.nbytes() + properties_nbytes
-        } else {
-            0
-        };
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.14.1] Fixed header
-            let first_byte = (PacketType::Disconnect as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.14.2] Variable header
-            if self.reason_code != DisconnectReasonCode::NormalDisconnection
-                || properties_nbytes > 0
-            {
-                self.reason_code.write(&mut cursor);
-                if properties_nbytes > 0 {
-                    properties_remaining_length.write(&mut cursor);
-                    write_properties!(&mut cursor, self, {
-                        PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
-                        PropertyType::ServerReference => server_reference: (Option<Py<PyString>>) = None,
-                        PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                        PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-                    });
-                }
-            }
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
 
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -2497,32 +2813,25 @@ impl DisconnectPacket {
 }
 
 impl DisconnectPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.14.2] Variable header
-        let reason_code = if cursor.index < cursor.buffer.len() {
-            DisconnectReasonCode::read(cursor)?
-        } else {
-            DisconnectReasonCode::NormalDisconnection
-        };
-        read_properties!("DisconnectPacket", cursor, {
-            PropertyType::SessionExpiryInterval => session_expiry_interval: (Option<u32>) = None,
-            PropertyType::ServerReference => server_reference: (Option<Py<PyString>>) = None,
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-
-        // Return the Python object
-        let packet = Self {
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.server_reference.check_size(py)?;
+        self.server_reference.check_format(py)?;
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
+
+    pub fn from_frame(py: Python, frame: DisconnectFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             reason_code,
             session_expiry_interval,
             server_reference,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -2537,13 +2846,47 @@ impl PartialEq for DisconnectPacket {
     }
 }
 
-#[pyclass(frozen, subclass, eq, get_all, module = "mqtt5")]
+#[pyclass(frozen, subclass, eq, module = "mqtt5")]
 pub struct AuthPacket {
+    #[pyo3(get)]
     pub reason_code: AuthReasonCode,
+    #[pyo3(get)]
     pub authentication_method: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub authentication_data: Option<Py<PyBytes>>,
+    #[pyo3(get)]
     pub reason_str: Option<Py<PyString>>,
+    #[pyo3(get)]
     pub user_properties: Py<PyList>,
+    cache: WriteCache,
+}
+
+impl Cached for AuthPacket {
+    fn cache(&self) -> &WriteCache {
+        &self.cache
+    }
+
+    fn lists(&self) -> Vec<&Py<PyList>> {
+        vec![&self.user_properties]
+    }
+}
+
+impl Framed for AuthPacket {
+    type Frame<'a> = AuthFrame<'a>;
+
+    fn frame<'a, 'py>(
+        &'a self,
+        py: Python<'py>,
+        backing: &'a Backing<'py>,
+    ) -> PyResult<AuthFrame<'a>> {
+        let user_properties = backing.extract(0)?;
+        Ok(to_frame!(AuthFrame, py, self, {
+            reason_code,
+            authentication_method,
+            authentication_data,
+            reason_str,
+        }, user_properties))
+    }
 }
 
 #[pymethods]
@@ -2561,55 +2904,52 @@ impl AuthPacket {
         py: Python<'_>,
         reason_code: AuthReasonCode,
         authentication_method: Option<Py<PyString>>,
-        authentication_data: Option<Py<PyBytes>>,
+        #[pyo3(from_py_with = extract_binary)] authentication_data: Option<Py<PyBytes>>,
         reason_str: Option<Py<PyString>>,
         user_properties: Option<Py<PyList>>,
     ) -> PyResult<Self> {
-        authentication_method.check_size(py)?;
-        authentication_data.check_size(py)?;
-        reason_str.check_size(py)?;
-        check_user_properties_size(py, user_properties.as_ref())?;
-        Ok(Self {
+        let packet = Self {
             reason_code,
             authentication_method,
             authentication_data,
             reason_str,
             user_properties: user_properties.unwrap_or_else(|| PyList::empty(py).unbind()),
-        })
+            cache: WriteCache::default(),
+        };
+        packet.validate(py)?;
+        Ok(packet)
     }
 
-    pub fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
-        let properties_nbytes = nbytes_properties!(self, {
-            PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-            PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-        let properties_remaining_length = VariableByteInteger::new(properties_nbytes);
-        let nbytes =
-            self.reason_code.nbytes() + properties_remaining_length.nbytes() + properties_nbytes;
-        let remaining_length = VariableByteInteger::new(nbytes);
-        PyBytes::new_with(py, 1 + remaining_length.nbytes() + nbytes, |buffer| {
-            let mut cursor = WriteCursor::new(buffer, 0);
-
-            // [3.15.1] Fixed header
-            let first_byte = (PacketType::Auth as u8) << 4;
-            first_byte.write(&mut cursor);
-            remaining_length.write(&mut cursor);
-
-            // [3.15.2] Variable header
-            self.reason_code.write(&mut cursor);
-            properties_remaining_length.write(&mut cursor);
-            write_properties!(&mut cursor, self, {
-                PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-                PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-                PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-                PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-            });
-
-            Ok(())
-        })
-        .map(|bytes| bytes.unbind())
+    #[pyo3(signature = (*, max_packet_size=None, version=ProtocolVersion::V5))]
+    pub fn write(
+        &self,
+        py: Python,
+        max_packet_size: Option<u32>,
+        version: ProtocolVersion,
+    ) -> PyResult<Py<PyBytes>> {
+        self.cache
+            .get_or_write(py, &self.lists(), max_packet_size, version, || {
+                let backing = self.backing(py)?;
+                let mut frame = self.frame(py, &backing)?;
+                reduce_frame!(frame, max_packet_size, version);
+                write_frame(py, &frame, max_packet_size, version)
+            })
+    }
+
+    #[pyo3(signature = (buffer, offset=0, *, version=ProtocolVersion::V5))]
+    pub fn write_into(
+        &self,
+        py: Python,
+        buffer: PyBuffer<u8>,
+        offset: usize,
+        version: ProtocolVersion,
+    ) -> PyResult<usize> {
+        write_packet_into(py, self, &buffer, offset, version)
+    }
+
+    #[pyo3(signature = (*, version=ProtocolVersion::V5))]
+    pub fn nbytes(&self, py: Python, version: ProtocolVersion) -> PyResult<usize> {
+        packet_nbytes(py, self, version)
     }
 
     fn __repr__(slf: &Bound<'_, Self>) -> String {
@@ -2626,32 +2966,26 @@ impl AuthPacket {
 }
 
 impl AuthPacket {
-    pub fn read(py: Python, cursor: &mut ReadCursor, flags: u8) -> PyResult<Py<Self>> {
-        if flags != 0x00 {
-            return Err(PyValueError::new_err("Invalid fixed header flags"));
-        }
-
-        // [3.15.2] Variable header
-        let reason_code = if cursor.index < cursor.buffer.len() {
-            AuthReasonCode::read(cursor)?
-        } else {
-            AuthReasonCode::Success
-        };
-        read_properties!("AuthPacket", cursor, {
-            PropertyType::AuthenticationMethod => authentication_method: (Option<Py<PyString>>) = None,
-            PropertyType::AuthenticationData => authentication_data: (Option<Py<PyBytes>>) = None,
-            PropertyType::ReasonStr => reason_str: (Option<Py<PyString>>) = None,
-            PropertyType::UserProperty => user_properties: (Py<PyList<UserProperty>>) = PyList::empty(py),
-        });
-
-        // Return the Python object
-        let packet = Self {
+    /// Checks the values against the constraints of the specification.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.authentication_method.check_size(py)?;
+        self.authentication_method.check_format(py)?;
+        self.authentication_data.check_size(py)?;
+        self.reason_str.check_size(py)?;
+        self.reason_str.check_format(py)?;
+        check_user_properties_size(py, &self.user_properties)?;
+        check_user_properties_format(py, &self.user_properties)
+    }
+
+    pub fn from_frame(py: Python, frame: AuthFrame<'_>) -> PyResult<Py<Self>> {
+        let packet = from_frame!(py, frame, {
             reason_code,
             authentication_method,
             authentication_data,
             reason_str,
-            user_properties: user_properties.unbind(),
-        };
+            user_properties,
+        });
+        packet.validate(py)?;
         Py::new(py, packet)
     }
 }
@@ -2667,3 +3001,107 @@ impl PartialEq for AuthPacket {
             && self.user_properties.py_eq(&other.user_properties)
     }
 }
+
+/// Reference to a packet of any type, to write packets whose type is only known at runtime.
+pub enum PacketRef<'a> {
+    Connect(&'a ConnectPacket),
+    ConnAck(&'a ConnAckPacket),
+    Publish(&'a PublishPacket),
+    PubAck(&'a PubAckPacket),
+    PubRec(&'a PubRecPacket),
+    PubRel(&'a PubRelPacket),
+    PubComp(&'a PubCompPacket),
+    Subscribe(&'a SubscribePacket),
+    SubAck(&'a SubAckPacket),
+    Unsubscribe(&'a UnsubscribePacket),
+    UnsubAck(&'a UnsubAckPacket),
+    PingReq(&'a PingReqPacket),
+    PingResp(&'a PingRespPacket),
+    Disconnect(&'a DisconnectPacket),
+    Auth(&'a AuthPacket),
+}
+
+impl<'a> PacketRef<'a> {
+    pub fn extract(packet: &'a Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(packet) = packet.cast::<ConnectPacket>() {
+            Ok(Self::Connect(packet.get()))
+        } else if let Ok(packet) = packet.cast::<ConnAckPacket>() {
+            Ok(Self::ConnAck(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PublishPacket>() {
+            Ok(Self::Publish(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PubAckPacket>() {
+            Ok(Self::PubAck(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PubRecPacket>() {
+            Ok(Self::PubRec(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PubRelPacket>() {
+            Ok(Self::PubRel(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PubCompPacket>() {
+            Ok(Self::PubComp(packet.get()))
+        } else if let Ok(packet) = packet.cast::<SubscribePacket>() {
+            Ok(Self::Subscribe(packet.get()))
+        } else if let Ok(packet) = packet.cast::<SubAckPacket>() {
+            Ok(Self::SubAck(packet.get()))
+        } else if let Ok(packet) = packet.cast::<UnsubscribePacket>() {
+            Ok(Self::Unsubscribe(packet.get()))
+        } else if let Ok(packet) = packet.cast::<UnsubAckPacket>() {
+            Ok(Self::UnsubAck(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PingReqPacket>() {
+            Ok(Self::PingReq(packet.get()))
+        } else if let Ok(packet) = packet.cast::<PingRespPacket>() {
+            Ok(Self::PingResp(packet.get()))
+        } else if let Ok(packet) = packet.cast::<DisconnectPacket>() {
+            Ok(Self::Disconnect(packet.get()))
+        } else if let Ok(packet) = packet.cast::<AuthPacket>() {
+            Ok(Self::Auth(packet.get()))
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "Expected a packet, got {}",
+                packet.get_type().name()?
+            )))
+        }
+    }
+
+    pub fn backing<'py>(&self, py: Python<'py>) -> PyResult<Backing<'py>> {
+        match self {
+            Self::Connect(packet) => packet.backing(py),
+            Self::ConnAck(packet) => packet.backing(py),
+            Self::Publish(packet) => packet.backing(py),
+            Self::PubAck(packet) => packet.backing(py),
+            Self::PubRec(packet) => packet.backing(py),
+            Self::PubRel(packet) => packet.backing(py),
+            Self::PubComp(packet) => packet.backing(py),
+            Self::Subscribe(packet) => packet.backing(py),
+            Self::SubAck(packet) => packet.backing(py),
+            Self::Unsubscribe(packet) => packet.backing(py),
+            Self::UnsubAck(packet) => packet.backing(py),
+            Self::PingReq(packet) => packet.backing(py),
+            Self::PingResp(packet) => packet.backing(py),
+            Self::Disconnect(packet) => packet.backing(py),
+            Self::Auth(packet) => packet.backing(py),
+        }
+    }
+
+    pub fn frame<'b, 'py>(&self, py: Python<'py>, backing: &'b Backing<'py>) -> PyResult<Frame<'b>>
+    where
+        'a: 'b,
+    {
+        let frame = match self {
+            Self::Connect(packet) => Frame::Connect(packet.frame(py, backing)?),
+            Self::ConnAck(packet) => Frame::ConnAck(packet.frame(py, backing)?),
+            Self::Publish(packet) => Frame::Publish(packet.frame(py, backing)?),
+            Self::PubAck(packet) => Frame::PubAck(packet.frame(py, backing)?),
+            Self::PubRec(packet) => Frame::PubRec(packet.frame(py, backing)?),
+            Self::PubRel(packet) => Frame::PubRel(packet.frame(py, backing)?),
+            Self::PubComp(packet) => Frame::PubComp(packet.frame(py, backing)?),
+            Self::Subscribe(packet) => Frame::Subscribe(packet.frame(py, backing)?),
+            Self::SubAck(packet) => Frame::SubAck(packet.frame(py, backing)?),
+            Self::Unsubscribe(packet) => Frame::Unsubscribe(packet.frame(py, backing)?),
+            Self::UnsubAck(packet) => Frame::UnsubAck(packet.frame(py, backing)?),
+            Self::PingReq(packet) => Frame::PingReq(packet.frame(py, backing)?),
+            Self::PingResp(packet) => Frame::PingResp(packet.frame(py, backing)?),
+            Self::Disconnect(packet) => Frame::Disconnect(packet.frame(py, backing)?),
+            Self::Auth(packet) => Frame::Auth(packet.frame(py, backing)?),
+        };
+        Ok(frame)
+    }
+}
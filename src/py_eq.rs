@@ -17,6 +17,12 @@ impl PyEq for Py<PyBytes> {
     }
 }
 
+impl PyEq for Py<PyAny> {
+    fn py_eq(&self, other: &Self) -> bool {
+        Python::attach(|py| self.bind(py).eq(other.bind(py)).unwrap_or(false))
+    }
+}
+
 impl PyEq for Py<PyList> {
     fn py_eq(&self, other: &Self) -> bool {
         Python::attach(|py| self.bind(py).as_any().eq(other.bind(py)).unwrap_or(false))
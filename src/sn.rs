@@ -0,0 +1,1464 @@
+use crate::check_format::check_string_format;
+use crate::enums::{QoS, py_int_enum};
+use crate::errors::{MalformedPacketError, PacketTooLargeError, ProtocolError, locate};
+use crate::io::{ReadCursor, Readable, Writable, WriteCursor, buffer_bytes};
+use crate::packets::{extract_binary, py_repr, repr_field};
+use crate::py_eq::PyEq;
+use num_enum::TryFromPrimitive;
+use pyo3::PyClass;
+use pyo3::PyResult;
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::pyclass_init::PyClassInitializer;
+use pyo3::types::{PyBytes, PyString};
+use std::fmt;
+
+py_int_enum! {
+    "mqtt5.sn",
+    PacketType {
+        Advertise = 0x00,
+        SearchGw = 0x01,
+        GwInfo = 0x02,
+        Connect = 0x04,
+        ConnAck = 0x05,
+        WillTopicReq = 0x06,
+        WillTopic = 0x07,
+        WillMsgReq = 0x08,
+        WillMsg = 0x09,
+        Register = 0x0a,
+        RegAck = 0x0b,
+        Publish = 0x0c,
+        PubAck = 0x0d,
+        PubComp = 0x0e,
+        PubRec = 0x0f,
+        PubRel = 0x10,
+        Subscribe = 0x12,
+        SubAck = 0x13,
+        Unsubscribe = 0x14,
+        UnsubAck = 0x15,
+        PingReq = 0x16,
+        PingResp = 0x17,
+        Disconnect = 0x18,
+        WillTopicUpd = 0x1a,
+        WillTopicResp = 0x1b,
+        WillMsgUpd = 0x1c,
+        WillMsgResp = 0x1d,
+    }
+}
+
+py_int_enum! {
+    "mqtt5.sn",
+    TopicIdType {
+        Normal = 0,
+        Predefined = 1,
+        ShortName = 2,
+    }
+}
+
+py_int_enum! {
+    "mqtt5.sn",
+    ReturnCode {
+        Accepted = 0,
+        RejectedCongestion = 1,
+        RejectedInvalidTopicId = 2,
+        RejectedNotSupported = 3,
+    }
+}
+
+const DUPLICATE: u8 = 0x80;
+const RETAIN: u8 = 0x10;
+const WILL: u8 = 0x08;
+const CLEAN_SESSION: u8 = 0x04;
+
+/// Protocol ID of MQTT-SN 1.2, the only one defined so far.
+const PROTOCOL_ID: u8 = 0x01;
+
+/// Returns the mask if the flag is set, and zero otherwise.
+fn flag(set: bool, mask: u8) -> u8 {
+    if set { mask } else { 0 }
+}
+
+fn read_qos(flags: u8) -> PyResult<QoS> {
+    QoS::new((flags >> 5) & 0x03)
+}
+
+fn qos_flags(qos: QoS) -> u8 {
+    (qos as u8) << 5
+}
+
+/// Extracts message data, copying the contents of buffers that aren't bytes objects.
+fn extract_data(value: &Bound<'_, PyAny>) -> PyResult<Py<PyBytes>> {
+    extract_binary(value)?.ok_or_else(|| PyTypeError::new_err("Expected bytes, got None"))
+}
+
+/// Reads the bytes up to the end of the message.
+fn read_rest<'a>(cursor: &mut ReadCursor<'a>) -> PyResult<&'a [u8]> {
+    cursor.read_slice(cursor.buffer.len() - cursor.index)
+}
+
+/// Reads a string of the given number of bytes, which MQTT-SN doesn't prefix with its length.
+fn read_str<'a>(cursor: &mut ReadCursor<'a>, length: usize) -> PyResult<&'a str> {
+    let bytes = cursor.read_slice(length)?;
+    let s =
+        str::from_utf8(bytes).map_err(|_| MalformedPacketError::new_err("Invalid UTF-8 string"))?;
+    check_string_format(s, false)?;
+    Ok(s)
+}
+
+/// Reads a string up to the end of the message.
+fn read_rest_str<'a>(cursor: &mut ReadCursor<'a>) -> PyResult<&'a str> {
+    read_str(cursor, cursor.buffer.len() - cursor.index)
+}
+
+/// Checks that the client id has 1 to 23 characters, as MQTT-SN requires.
+fn check_client_id(client_id: &str) -> PyResult<()> {
+    check_string_format(client_id, false)?;
+    if !(1..=23).contains(&client_id.chars().count()) {
+        return Err(ProtocolError::new_err(
+            "Client id must have 1 to 23 characters",
+        ));
+    }
+    Ok(())
+}
+
+/// Topic of a message, which is either a topic ID or a topic name depending on its type.
+#[derive(Clone, PartialEq)]
+pub enum Topic {
+    Id(u16),
+    Name(String),
+}
+
+impl Topic {
+    /// Checks that the topic matches the topic ID type.
+    ///
+    /// Subscriptions carry normal topics as topic names, while all other messages carry them as
+    /// topic IDs that were registered before.
+    fn check(&self, topic_id_type: TopicIdType, subscription: bool) -> PyResult<()> {
+        if let Self::Name(name) = self {
+            check_string_format(name, false)?;
+        }
+        let name_expected = match topic_id_type {
+            TopicIdType::Normal => subscription,
+            TopicIdType::Predefined => false,
+            TopicIdType::ShortName => true,
+        };
+        match self {
+            Self::Id(_) if name_expected => Err(ProtocolError::new_err(format!(
+                "Topic must be a topic name for topic ID type {}",
+                topic_id_type.name()?
+            ))),
+            Self::Name(_) if !name_expected => Err(ProtocolError::new_err(format!(
+                "Topic must be a topic ID for topic ID type {}",
+                topic_id_type.name()?
+            ))),
+            Self::Name(name) if topic_id_type == TopicIdType::ShortName && name.len() != 2 => {
+                Err(ProtocolError::new_err("Short topic name must have 2 bytes"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn read(
+        cursor: &mut ReadCursor<'_>,
+        topic_id_type: TopicIdType,
+        subscription: bool,
+    ) -> PyResult<Self> {
+        match topic_id_type {
+            TopicIdType::Normal if subscription => {
+                Ok(Self::Name(read_rest_str(cursor)?.to_owned()))
+            },
+            TopicIdType::ShortName => Ok(Self::Name(read_str(cursor, 2)?.to_owned())),
+            _ => Ok(Self::Id(u16::read(cursor)?)),
+        }
+    }
+}
+
+impl Writable for Topic {
+    fn write(&self, cursor: &mut WriteCursor<'_>) {
+        match self {
+            Self::Id(topic_id) => topic_id.write(cursor),
+            Self::Name(name) => cursor.write_slice(name.as_bytes()),
+        }
+    }
+
+    fn nbytes(&self) -> usize {
+        match self {
+            Self::Id(_) => 2,
+            Self::Name(name) => name.len(),
+        }
+    }
+}
+
+impl<'a, 'py> FromPyObject<'a, 'py> for Topic {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        if let Ok(name) = obj.cast::<PyString>() {
+            return Ok(Self::Name(name.to_str()?.to_owned()));
+        }
+        Ok(Self::Id(obj.extract()?))
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &Topic {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+        match self {
+            Topic::Id(topic_id) => Ok(topic_id.into_pyobject(py)?.into_any()),
+            Topic::Name(name) => Ok(PyString::new(py, name).into_any()),
+        }
+    }
+}
+
+/// Message that is framed by its length and type.
+trait Message {
+    const PACKET_TYPE: PacketType;
+
+    fn read(py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self>
+    where
+        Self: Sized;
+
+    fn body_nbytes(&self, py: Python) -> usize;
+
+    fn write_body(&self, py: Python, cursor: &mut WriteCursor<'_>);
+}
+
+/// Writes a message with a one-byte length field, or with a three-byte one if it doesn't fit.
+fn write_message<T: Message>(py: Python, message: &T) -> PyResult<Py<PyBytes>> {
+    // The length counts the whole message, including the length field and the message type
+    let nbytes = match message.body_nbytes(py) + 2 {
+        nbytes @ ..=255 => nbytes,
+        nbytes => nbytes + 2,
+    };
+    if nbytes > u16::MAX as usize {
+        return Err(PacketTooLargeError::new_err(
+            "Message size exceeds 65535 bytes",
+        ));
+    }
+    PyBytes::new_with(py, nbytes, |buffer| {
+        let mut cursor = WriteCursor::new(buffer, 0);
+        if nbytes <= 255 {
+            (nbytes as u8).write(&mut cursor);
+        } else {
+            0x01u8.write(&mut cursor);
+            (nbytes as u16).write(&mut cursor);
+        }
+        T::PACKET_TYPE.write(&mut cursor);
+        message.write_body(py, &mut cursor);
+        Ok(())
+    })
+    .map(Bound::unbind)
+}
+
+/// Reads the length field and binds the cursor to the rest of the message.
+fn read_length(cursor: &mut ReadCursor<'_>) -> PyResult<()> {
+    let length = match u8::read(cursor)? {
+        0x01 => usize::from(u16::read(cursor)?),
+        length => usize::from(length),
+    };
+    // The message type has to fit in as well
+    if length <= cursor.index {
+        return Err(MalformedPacketError::new_err("Invalid length"));
+    }
+    cursor.bind(length - cursor.index)
+}
+
+fn read_into<T>(py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Py<PyAny>>
+where
+    T: Message + PyClass + Into<PyClassInitializer<T>>,
+{
+    Ok(Py::new(py, T::read(py, cursor)?)?.into_any())
+}
+
+/// Reads the message that the cursor is bound to, starting at its type.
+fn read_message(py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Py<PyAny>> {
+    let packet = match PacketType::read(cursor)? {
+        PacketType::Advertise => read_into::<AdvertisePacket>(py, cursor)?,
+        PacketType::SearchGw => read_into::<SearchGwPacket>(py, cursor)?,
+        PacketType::GwInfo => read_into::<GwInfoPacket>(py, cursor)?,
+        PacketType::Connect => read_into::<ConnectPacket>(py, cursor)?,
+        PacketType::ConnAck => read_into::<ConnAckPacket>(py, cursor)?,
+        PacketType::WillTopicReq => read_into::<WillTopicReqPacket>(py, cursor)?,
+        PacketType::WillTopic => read_into::<WillTopicPacket>(py, cursor)?,
+        PacketType::WillMsgReq => read_into::<WillMsgReqPacket>(py, cursor)?,
+        PacketType::WillMsg => read_into::<WillMsgPacket>(py, cursor)?,
+        PacketType::Register => read_into::<RegisterPacket>(py, cursor)?,
+        PacketType::RegAck => read_into::<RegAckPacket>(py, cursor)?,
+        PacketType::Publish => read_into::<PublishPacket>(py, cursor)?,
+        PacketType::PubAck => read_into::<PubAckPacket>(py, cursor)?,
+        PacketType::PubComp => read_into::<PubCompPacket>(py, cursor)?,
+        PacketType::PubRec => read_into::<PubRecPacket>(py, cursor)?,
+        PacketType::PubRel => read_into::<PubRelPacket>(py, cursor)?,
+        PacketType::Subscribe => read_into::<SubscribePacket>(py, cursor)?,
+        PacketType::SubAck => read_into::<SubAckPacket>(py, cursor)?,
+        PacketType::Unsubscribe => read_into::<UnsubscribePacket>(py, cursor)?,
+        PacketType::UnsubAck => read_into::<UnsubAckPacket>(py, cursor)?,
+        PacketType::PingReq => read_into::<PingReqPacket>(py, cursor)?,
+        PacketType::PingResp => read_into::<PingRespPacket>(py, cursor)?,
+        PacketType::Disconnect => read_into::<DisconnectPacket>(py, cursor)?,
+        PacketType::WillTopicUpd => read_into::<WillTopicUpdPacket>(py, cursor)?,
+        PacketType::WillTopicResp => read_into::<WillTopicRespPacket>(py, cursor)?,
+        PacketType::WillMsgUpd => read_into::<WillMsgUpdPacket>(py, cursor)?,
+        PacketType::WillMsgResp => read_into::<WillMsgRespPacket>(py, cursor)?,
+    };
+    if cursor.index != cursor.buffer.len() {
+        return Err(MalformedPacketError::new_err("Invalid length"));
+    }
+    Ok(packet)
+}
+
+#[pyfunction]
+pub fn read(py: Python, buffer: PyBuffer<u8>) -> PyResult<(Py<PyAny>, usize)> {
+    let mut cursor = ReadCursor::new(buffer_bytes(&buffer)?);
+    let packet = read_length(&mut cursor)
+        .and_then(|_| read_message(py, &mut cursor))
+        .map_err(|error| locate(py, error, None, Some(cursor.index)))?;
+    Ok((packet, cursor.index))
+}
+
+/// Defines a message without a body.
+macro_rules! empty_message {
+    ($name:ident, $packet_type:ident) => {
+        #[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+        #[derive(PartialEq)]
+        pub struct $name {}
+
+        impl Message for $name {
+            const PACKET_TYPE: PacketType = PacketType::$packet_type;
+
+            fn read(_py: Python, _cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+                Ok(Self {})
+            }
+
+            fn body_nbytes(&self, _py: Python) -> usize {
+                0
+            }
+
+            fn write_body(&self, _py: Python, _cursor: &mut WriteCursor<'_>) {}
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            fn new() -> Self {
+                Self {}
+            }
+
+            fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+                write_message(py, self)
+            }
+
+            fn __repr__(_slf: &Bound<'_, Self>) -> String {
+                py_repr!($name)
+            }
+        }
+    };
+}
+
+/// Defines a message that consists of a packet ID.
+macro_rules! packet_id_message {
+    ($name:ident, $packet_type:ident) => {
+        #[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+        #[derive(PartialEq)]
+        pub struct $name {
+            #[pyo3(get)]
+            packet_id: u16,
+        }
+
+        impl Message for $name {
+            const PACKET_TYPE: PacketType = PacketType::$packet_type;
+
+            fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+                let packet_id = u16::read(cursor)?;
+                Ok(Self { packet_id })
+            }
+
+            fn body_nbytes(&self, _py: Python) -> usize {
+                2
+            }
+
+            fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+                self.packet_id.write(cursor);
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            fn new(packet_id: u16) -> Self {
+                Self { packet_id }
+            }
+
+            fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+                write_message(py, self)
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> String {
+                py_repr!(slf, $name, packet_id)
+            }
+        }
+    };
+}
+
+/// Defines a message that consists of a return code.
+macro_rules! return_code_message {
+    ($name:ident, $packet_type:ident) => {
+        #[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+        #[derive(PartialEq)]
+        pub struct $name {
+            #[pyo3(get)]
+            return_code: ReturnCode,
+        }
+
+        impl Message for $name {
+            const PACKET_TYPE: PacketType = PacketType::$packet_type;
+
+            fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+                let return_code = ReturnCode::read(cursor)?;
+                Ok(Self { return_code })
+            }
+
+            fn body_nbytes(&self, _py: Python) -> usize {
+                1
+            }
+
+            fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+                self.return_code.write(cursor);
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            #[pyo3(signature = (*, return_code=ReturnCode::Accepted))]
+            fn new(return_code: ReturnCode) -> Self {
+                Self { return_code }
+            }
+
+            fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+                write_message(py, self)
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> String {
+                py_repr!(slf, $name, return_code)
+            }
+        }
+    };
+}
+
+/// Defines a message that carries the will topic, or clears it if it's empty.
+macro_rules! will_topic_message {
+    ($name:ident, $packet_type:ident) => {
+        #[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+        #[derive(PartialEq)]
+        pub struct $name {
+            #[pyo3(get)]
+            topic: Option<String>,
+            #[pyo3(get)]
+            qos: QoS,
+            #[pyo3(get)]
+            retain: bool,
+        }
+
+        impl $name {
+            fn check(&self) -> PyResult<()> {
+                if let Some(topic) = &self.topic {
+                    check_string_format(topic, false)?;
+                }
+                if self.topic.is_none() && (self.qos != QoS::AtMostOnce || self.retain) {
+                    return Err(ProtocolError::new_err(
+                        "QoS and retain must not be set without topic",
+                    ));
+                }
+                Ok(())
+            }
+        }
+
+        impl Message for $name {
+            const PACKET_TYPE: PacketType = PacketType::$packet_type;
+
+            fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+                // An empty message deletes the will topic
+                if cursor.index == cursor.buffer.len() {
+                    return Ok(Self {
+                        topic: None,
+                        qos: QoS::AtMostOnce,
+                        retain: false,
+                    });
+                }
+                let flags = u8::read(cursor)?;
+                let topic = read_rest_str(cursor)?;
+                Ok(Self {
+                    topic: Some(topic.to_owned()),
+                    qos: read_qos(flags)?,
+                    retain: flags & RETAIN != 0,
+                })
+            }
+
+            fn body_nbytes(&self, _py: Python) -> usize {
+                self.topic.as_ref().map_or(0, |topic| 1 + topic.len())
+            }
+
+            fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+                if let Some(topic) = &self.topic {
+                    let flags = qos_flags(self.qos) | flag(self.retain, RETAIN);
+                    flags.write(cursor);
+                    cursor.write_slice(topic.as_bytes());
+                }
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            #[pyo3(signature = (topic=None, *, qos=QoS::AtMostOnce, retain=false))]
+            fn new(topic: Option<String>, qos: QoS, retain: bool) -> PyResult<Self> {
+                let packet = Self { topic, qos, retain };
+                packet.check()?;
+                Ok(packet)
+            }
+
+            fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+                write_message(py, self)
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> String {
+                py_repr!(slf, $name, topic, qos, retain)
+            }
+        }
+    };
+}
+
+/// Defines a message that carries the will message.
+macro_rules! will_msg_message {
+    ($name:ident, $packet_type:ident) => {
+        #[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+        pub struct $name {
+            #[pyo3(get)]
+            payload: Py<PyBytes>,
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.payload.py_eq(&other.payload)
+            }
+        }
+
+        impl Message for $name {
+            const PACKET_TYPE: PacketType = PacketType::$packet_type;
+
+            fn read(py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+                let payload = read_rest(cursor)?;
+                Ok(Self {
+                    payload: PyBytes::new(py, payload).unbind(),
+                })
+            }
+
+            fn body_nbytes(&self, py: Python) -> usize {
+                self.payload.bind(py).as_bytes().len()
+            }
+
+            fn write_body(&self, py: Python, cursor: &mut WriteCursor<'_>) {
+                cursor.write_slice(self.payload.bind(py).as_bytes());
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            fn new(#[pyo3(from_py_with = extract_data)] payload: Py<PyBytes>) -> Self {
+                Self { payload }
+            }
+
+            fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+                write_message(py, self)
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> String {
+                py_repr!(slf, $name, payload)
+            }
+        }
+    };
+}
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct AdvertisePacket {
+    #[pyo3(get)]
+    gateway_id: u8,
+    #[pyo3(get)]
+    duration: u16,
+}
+
+impl Message for AdvertisePacket {
+    const PACKET_TYPE: PacketType = PacketType::Advertise;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let gateway_id = u8::read(cursor)?;
+        let duration = u16::read(cursor)?;
+        Ok(Self {
+            gateway_id,
+            duration,
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        3
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        self.gateway_id.write(cursor);
+        self.duration.write(cursor);
+    }
+}
+
+#[pymethods]
+impl AdvertisePacket {
+    #[new]
+    fn new(gateway_id: u8, duration: u16) -> Self {
+        Self {
+            gateway_id,
+            duration,
+        }
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, AdvertisePacket, gateway_id, duration)
+    }
+}
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct SearchGwPacket {
+    #[pyo3(get)]
+    radius: u8,
+}
+
+impl Message for SearchGwPacket {
+    const PACKET_TYPE: PacketType = PacketType::SearchGw;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let radius = u8::read(cursor)?;
+        Ok(Self { radius })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        1
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        self.radius.write(cursor);
+    }
+}
+
+#[pymethods]
+impl SearchGwPacket {
+    #[new]
+    fn new(radius: u8) -> Self {
+        Self { radius }
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, SearchGwPacket, radius)
+    }
+}
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+pub struct GwInfoPacket {
+    #[pyo3(get)]
+    gateway_id: u8,
+    #[pyo3(get)]
+    gateway_address: Option<Py<PyBytes>>,
+}
+
+impl PartialEq for GwInfoPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.gateway_id == other.gateway_id && self.gateway_address.py_eq(&other.gateway_address)
+    }
+}
+
+impl Message for GwInfoPacket {
+    const PACKET_TYPE: PacketType = PacketType::GwInfo;
+
+    fn read(py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let gateway_id = u8::read(cursor)?;
+        // Gateways leave out their own address, clients that answer for them add it
+        let gateway_address = read_rest(cursor)?;
+        Ok(Self {
+            gateway_id,
+            gateway_address: (!gateway_address.is_empty())
+                .then(|| PyBytes::new(py, gateway_address).unbind()),
+        })
+    }
+
+    fn body_nbytes(&self, py: Python) -> usize {
+        1 + self
+            .gateway_address
+            .as_ref()
+            .map_or(0, |address| address.bind(py).as_bytes().len())
+    }
+
+    fn write_body(&self, py: Python, cursor: &mut WriteCursor<'_>) {
+        self.gateway_id.write(cursor);
+        if let Some(address) = &self.gateway_address {
+            cursor.write_slice(address.bind(py).as_bytes());
+        }
+    }
+}
+
+#[pymethods]
+impl GwInfoPacket {
+    #[new]
+    #[pyo3(signature = (gateway_id, gateway_address=None))]
+    fn new(
+        gateway_id: u8,
+        #[pyo3(from_py_with = extract_binary)] gateway_address: Option<Py<PyBytes>>,
+    ) -> Self {
+        Self {
+            gateway_id,
+            gateway_address,
+        }
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, GwInfoPacket, gateway_id, gateway_address)
+    }
+}
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct ConnectPacket {
+    #[pyo3(get)]
+    client_id: String,
+    #[pyo3(get)]
+    duration: u16,
+    #[pyo3(get)]
+    clean_session: bool,
+    #[pyo3(get)]
+    will: bool,
+}
+
+impl Message for ConnectPacket {
+    const PACKET_TYPE: PacketType = PacketType::Connect;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let flags = u8::read(cursor)?;
+        if u8::read(cursor)? != PROTOCOL_ID {
+            return Err(ProtocolError::new_err("Invalid protocol ID"));
+        }
+        let duration = u16::read(cursor)?;
+        let client_id = read_rest_str(cursor)?;
+        check_client_id(client_id)?;
+        Ok(Self {
+            client_id: client_id.to_owned(),
+            duration,
+            clean_session: flags & CLEAN_SESSION != 0,
+            will: flags & WILL != 0,
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        4 + self.client_id.len()
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        let flags = flag(self.will, WILL) | flag(self.clean_session, CLEAN_SESSION);
+        flags.write(cursor);
+        PROTOCOL_ID.write(cursor);
+        self.duration.write(cursor);
+        cursor.write_slice(self.client_id.as_bytes());
+    }
+}
+
+#[pymethods]
+impl ConnectPacket {
+    #[new]
+    #[pyo3(signature = (client_id, *, duration=0, clean_session=false, will=false))]
+    fn new(client_id: String, duration: u16, clean_session: bool, will: bool) -> PyResult<Self> {
+        check_client_id(&client_id)?;
+        Ok(Self {
+            client_id,
+            duration,
+            clean_session,
+            will,
+        })
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, ConnectPacket, client_id, duration, clean_session, will)
+    }
+}
+
+return_code_message!(ConnAckPacket, ConnAck);
+empty_message!(WillTopicReqPacket, WillTopicReq);
+will_topic_message!(WillTopicPacket, WillTopic);
+empty_message!(WillMsgReqPacket, WillMsgReq);
+will_msg_message!(WillMsgPacket, WillMsg);
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct RegisterPacket {
+    #[pyo3(get)]
+    topic_id: u16,
+    #[pyo3(get)]
+    packet_id: u16,
+    #[pyo3(get)]
+    topic: String,
+}
+
+impl Message for RegisterPacket {
+    const PACKET_TYPE: PacketType = PacketType::Register;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let topic_id = u16::read(cursor)?;
+        let packet_id = u16::read(cursor)?;
+        let topic = read_rest_str(cursor)?;
+        Ok(Self {
+            topic_id,
+            packet_id,
+            topic: topic.to_owned(),
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        4 + self.topic.len()
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        self.topic_id.write(cursor);
+        self.packet_id.write(cursor);
+        cursor.write_slice(self.topic.as_bytes());
+    }
+}
+
+#[pymethods]
+impl RegisterPacket {
+    #[new]
+    fn new(topic_id: u16, packet_id: u16, topic: String) -> PyResult<Self> {
+        check_string_format(&topic, false)?;
+        Ok(Self {
+            topic_id,
+            packet_id,
+            topic,
+        })
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, RegisterPacket, topic_id, packet_id, topic)
+    }
+}
+
+/// Defines a message that acknowledges a topic ID with a return code.
+macro_rules! topic_ack_message {
+    ($name:ident, $packet_type:ident) => {
+        #[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+        #[derive(PartialEq)]
+        pub struct $name {
+            #[pyo3(get)]
+            topic_id: u16,
+            #[pyo3(get)]
+            packet_id: u16,
+            #[pyo3(get)]
+            return_code: ReturnCode,
+        }
+
+        impl Message for $name {
+            const PACKET_TYPE: PacketType = PacketType::$packet_type;
+
+            fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+                let topic_id = u16::read(cursor)?;
+                let packet_id = u16::read(cursor)?;
+                let return_code = ReturnCode::read(cursor)?;
+                Ok(Self {
+                    topic_id,
+                    packet_id,
+                    return_code,
+                })
+            }
+
+            fn body_nbytes(&self, _py: Python) -> usize {
+                5
+            }
+
+            fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+                self.topic_id.write(cursor);
+                self.packet_id.write(cursor);
+                self.return_code.write(cursor);
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            #[pyo3(signature = (topic_id, packet_id, *, return_code=ReturnCode::Accepted))]
+            fn new(topic_id: u16, packet_id: u16, return_code: ReturnCode) -> Self {
+                Self {
+                    topic_id,
+                    packet_id,
+                    return_code,
+                }
+            }
+
+            fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+                write_message(py, self)
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> String {
+                py_repr!(slf, $name, topic_id, packet_id, return_code)
+            }
+        }
+    };
+}
+
+topic_ack_message!(RegAckPacket, RegAck);
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+pub struct PublishPacket {
+    #[pyo3(get)]
+    topic: Topic,
+    #[pyo3(get)]
+    payload: Py<PyBytes>,
+    #[pyo3(get)]
+    topic_id_type: TopicIdType,
+    #[pyo3(get)]
+    qos: Option<QoS>,
+    #[pyo3(get)]
+    retain: bool,
+    #[pyo3(get)]
+    packet_id: Option<u16>,
+    #[pyo3(get)]
+    duplicate: bool,
+}
+
+impl PartialEq for PublishPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.topic == other.topic
+            && self.payload.py_eq(&other.payload)
+            && self.topic_id_type == other.topic_id_type
+            && self.qos == other.qos
+            && self.retain == other.retain
+            && self.packet_id == other.packet_id
+            && self.duplicate == other.duplicate
+    }
+}
+
+impl PublishPacket {
+    fn check(&self) -> PyResult<()> {
+        self.topic.check(self.topic_id_type, false)?;
+        // Clients publish with QoS -1 without connecting, so they can't register topics
+        match self.qos {
+            None if self.topic_id_type == TopicIdType::Normal => Err(ProtocolError::new_err(
+                "QoS=-1 requires a predefined topic ID or a short topic name",
+            )),
+            None | Some(QoS::AtMostOnce) if self.packet_id.is_some() => Err(
+                ProtocolError::new_err("Packet ID must not be set for QoS=0 and QoS=-1"),
+            ),
+            Some(QoS::AtLeastOnce | QoS::ExactlyOnce) if self.packet_id.is_none() => Err(
+                ProtocolError::new_err("Packet ID must be set for QoS=1 and QoS=2"),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Message for PublishPacket {
+    const PACKET_TYPE: PacketType = PacketType::Publish;
+
+    fn read(py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let flags = u8::read(cursor)?;
+        let qos = match (flags >> 5) & 0x03 {
+            0x03 => None,
+            _ => Some(read_qos(flags)?),
+        };
+        let topic_id_type = TopicIdType::new(flags & 0x03)?;
+        let topic = Topic::read(cursor, topic_id_type, false)?;
+        // The packet ID is only relevant for QoS=1 and QoS=2, and zero otherwise
+        let packet_id = u16::read(cursor)?;
+        let packet_id =
+            matches!(qos, Some(QoS::AtLeastOnce | QoS::ExactlyOnce)).then_some(packet_id);
+        let payload = read_rest(cursor)?;
+        let packet = Self {
+            topic,
+            payload: PyBytes::new(py, payload).unbind(),
+            topic_id_type,
+            qos,
+            retain: flags & RETAIN != 0,
+            packet_id,
+            duplicate: flags & DUPLICATE != 0,
+        };
+        packet.check()?;
+        Ok(packet)
+    }
+
+    fn body_nbytes(&self, py: Python) -> usize {
+        5 + self.payload.bind(py).as_bytes().len()
+    }
+
+    fn write_body(&self, py: Python, cursor: &mut WriteCursor<'_>) {
+        let flags = flag(self.duplicate, DUPLICATE)
+            | self.qos.map_or(0x03 << 5, qos_flags)
+            | flag(self.retain, RETAIN)
+            | self.topic_id_type as u8;
+        flags.write(cursor);
+        self.topic.write(cursor);
+        self.packet_id.unwrap_or(0).write(cursor);
+        cursor.write_slice(self.payload.bind(py).as_bytes());
+    }
+}
+
+#[pymethods]
+impl PublishPacket {
+    #[new]
+    #[pyo3(signature = (
+        topic,
+        payload,
+        *,
+        topic_id_type=TopicIdType::Normal,
+        qos=Some(QoS::AtMostOnce),
+        retain=false,
+        packet_id=None,
+        duplicate=false,
+    ))]
+    fn new(
+        topic: Topic,
+        #[pyo3(from_py_with = extract_data)] payload: Py<PyBytes>,
+        topic_id_type: TopicIdType,
+        qos: Option<QoS>,
+        retain: bool,
+        packet_id: Option<u16>,
+        duplicate: bool,
+    ) -> PyResult<Self> {
+        let packet = Self {
+            topic,
+            payload,
+            topic_id_type,
+            qos,
+            retain,
+            packet_id,
+            duplicate,
+        };
+        packet.check()?;
+        Ok(packet)
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(
+            slf,
+            PublishPacket,
+            topic,
+            payload,
+            topic_id_type,
+            qos,
+            retain,
+            packet_id,
+            duplicate,
+        )
+    }
+}
+
+topic_ack_message!(PubAckPacket, PubAck);
+packet_id_message!(PubCompPacket, PubComp);
+packet_id_message!(PubRecPacket, PubRec);
+packet_id_message!(PubRelPacket, PubRel);
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct SubscribePacket {
+    #[pyo3(get)]
+    packet_id: u16,
+    #[pyo3(get)]
+    topic: Topic,
+    #[pyo3(get)]
+    topic_id_type: TopicIdType,
+    #[pyo3(get)]
+    qos: QoS,
+    #[pyo3(get)]
+    duplicate: bool,
+}
+
+impl Message for SubscribePacket {
+    const PACKET_TYPE: PacketType = PacketType::Subscribe;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let flags = u8::read(cursor)?;
+        let topic_id_type = TopicIdType::new(flags & 0x03)?;
+        let packet_id = u16::read(cursor)?;
+        let topic = Topic::read(cursor, topic_id_type, true)?;
+        Ok(Self {
+            packet_id,
+            topic,
+            topic_id_type,
+            qos: read_qos(flags)?,
+            duplicate: flags & DUPLICATE != 0,
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        3 + self.topic.nbytes()
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        let flags =
+            flag(self.duplicate, DUPLICATE) | qos_flags(self.qos) | self.topic_id_type as u8;
+        flags.write(cursor);
+        self.packet_id.write(cursor);
+        self.topic.write(cursor);
+    }
+}
+
+#[pymethods]
+impl SubscribePacket {
+    #[new]
+    #[pyo3(signature = (
+        packet_id,
+        topic,
+        *,
+        topic_id_type=TopicIdType::Normal,
+        qos=QoS::AtMostOnce,
+        duplicate=false,
+    ))]
+    fn new(
+        packet_id: u16,
+        topic: Topic,
+        topic_id_type: TopicIdType,
+        qos: QoS,
+        duplicate: bool,
+    ) -> PyResult<Self> {
+        topic.check(topic_id_type, true)?;
+        Ok(Self {
+            packet_id,
+            topic,
+            topic_id_type,
+            qos,
+            duplicate,
+        })
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(
+            slf,
+            SubscribePacket,
+            packet_id,
+            topic,
+            topic_id_type,
+            qos,
+            duplicate,
+        )
+    }
+}
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct SubAckPacket {
+    #[pyo3(get)]
+    topic_id: u16,
+    #[pyo3(get)]
+    packet_id: u16,
+    #[pyo3(get)]
+    qos: QoS,
+    #[pyo3(get)]
+    return_code: ReturnCode,
+}
+
+impl Message for SubAckPacket {
+    const PACKET_TYPE: PacketType = PacketType::SubAck;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let flags = u8::read(cursor)?;
+        let topic_id = u16::read(cursor)?;
+        let packet_id = u16::read(cursor)?;
+        let return_code = ReturnCode::read(cursor)?;
+        Ok(Self {
+            topic_id,
+            packet_id,
+            qos: read_qos(flags)?,
+            return_code,
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        6
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        qos_flags(self.qos).write(cursor);
+        self.topic_id.write(cursor);
+        self.packet_id.write(cursor);
+        self.return_code.write(cursor);
+    }
+}
+
+#[pymethods]
+impl SubAckPacket {
+    #[new]
+    #[pyo3(signature = (
+        topic_id,
+        packet_id,
+        *,
+        qos=QoS::AtMostOnce,
+        return_code=ReturnCode::Accepted,
+    ))]
+    fn new(topic_id: u16, packet_id: u16, qos: QoS, return_code: ReturnCode) -> Self {
+        Self {
+            topic_id,
+            packet_id,
+            qos,
+            return_code,
+        }
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, SubAckPacket, topic_id, packet_id, qos, return_code)
+    }
+}
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct UnsubscribePacket {
+    #[pyo3(get)]
+    packet_id: u16,
+    #[pyo3(get)]
+    topic: Topic,
+    #[pyo3(get)]
+    topic_id_type: TopicIdType,
+}
+
+impl Message for UnsubscribePacket {
+    const PACKET_TYPE: PacketType = PacketType::Unsubscribe;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        let flags = u8::read(cursor)?;
+        let topic_id_type = TopicIdType::new(flags & 0x03)?;
+        let packet_id = u16::read(cursor)?;
+        let topic = Topic::read(cursor, topic_id_type, true)?;
+        Ok(Self {
+            packet_id,
+            topic,
+            topic_id_type,
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        3 + self.topic.nbytes()
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        (self.topic_id_type as u8).write(cursor);
+        self.packet_id.write(cursor);
+        self.topic.write(cursor);
+    }
+}
+
+#[pymethods]
+impl UnsubscribePacket {
+    #[new]
+    #[pyo3(signature = (packet_id, topic, *, topic_id_type=TopicIdType::Normal))]
+    fn new(packet_id: u16, topic: Topic, topic_id_type: TopicIdType) -> PyResult<Self> {
+        topic.check(topic_id_type, true)?;
+        Ok(Self {
+            packet_id,
+            topic,
+            topic_id_type,
+        })
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, UnsubscribePacket, packet_id, topic, topic_id_type)
+    }
+}
+
+packet_id_message!(UnsubAckPacket, UnsubAck);
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct PingReqPacket {
+    #[pyo3(get)]
+    client_id: Option<String>,
+}
+
+impl Message for PingReqPacket {
+    const PACKET_TYPE: PacketType = PacketType::PingReq;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        // Sleeping clients send their client id to wake up and receive buffered messages
+        let client_id = read_rest_str(cursor)?;
+        if client_id.is_empty() {
+            return Ok(Self { client_id: None });
+        }
+        check_client_id(client_id)?;
+        Ok(Self {
+            client_id: Some(client_id.to_owned()),
+        })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        self.client_id.as_ref().map_or(0, String::len)
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        if let Some(client_id) = &self.client_id {
+            cursor.write_slice(client_id.as_bytes());
+        }
+    }
+}
+
+#[pymethods]
+impl PingReqPacket {
+    #[new]
+    #[pyo3(signature = (client_id=None))]
+    fn new(client_id: Option<String>) -> PyResult<Self> {
+        if let Some(client_id) = &client_id {
+            check_client_id(client_id)?;
+        }
+        Ok(Self { client_id })
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, PingReqPacket, client_id)
+    }
+}
+
+empty_message!(PingRespPacket, PingResp);
+
+#[pyclass(frozen, subclass, eq, module = "mqtt5.sn")]
+#[derive(PartialEq)]
+pub struct DisconnectPacket {
+    #[pyo3(get)]
+    duration: Option<u16>,
+}
+
+impl Message for DisconnectPacket {
+    const PACKET_TYPE: PacketType = PacketType::Disconnect;
+
+    fn read(_py: Python, cursor: &mut ReadCursor<'_>) -> PyResult<Self> {
+        // Clients that go to sleep send the duration of their sleep
+        let duration = match cursor.index == cursor.buffer.len() {
+            true => None,
+            false => Some(u16::read(cursor)?),
+        };
+        Ok(Self { duration })
+    }
+
+    fn body_nbytes(&self, _py: Python) -> usize {
+        self.duration.map_or(0, |_| 2)
+    }
+
+    fn write_body(&self, _py: Python, cursor: &mut WriteCursor<'_>) {
+        if let Some(duration) = self.duration {
+            duration.write(cursor);
+        }
+    }
+}
+
+#[pymethods]
+impl DisconnectPacket {
+    #[new]
+    #[pyo3(signature = (duration=None))]
+    fn new(duration: Option<u16>) -> Self {
+        Self { duration }
+    }
+
+    fn write(&self, py: Python) -> PyResult<Py<PyBytes>> {
+        write_message(py, self)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> String {
+        py_repr!(slf, DisconnectPacket, duration)
+    }
+}
+
+will_topic_message!(WillTopicUpdPacket, WillTopicUpd);
+return_code_message!(WillTopicRespPacket, WillTopicResp);
+will_msg_message!(WillMsgUpdPacket, WillMsgUpd);
+return_code_message!(WillMsgRespPacket, WillMsgResp);
+
+/// Adds the `mqtt5.sn` submodule with the MQTT-SN messages to the package.
+pub fn add_module(parent: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = parent.py();
+    // The qualified name lets pickle and importlib find the classes of the submodule
+    let m = PyModule::new(py, "mqtt5.sn")?;
+    // Enums
+    m.add_class::<PacketType>()?;
+    m.add_class::<TopicIdType>()?;
+    m.add_class::<ReturnCode>()?;
+    // Packets
+    m.add_class::<AdvertisePacket>()?;
+    m.add_class::<SearchGwPacket>()?;
+    m.add_class::<GwInfoPacket>()?;
+    m.add_class::<ConnectPacket>()?;
+    m.add_class::<ConnAckPacket>()?;
+    m.add_class::<WillTopicReqPacket>()?;
+    m.add_class::<WillTopicPacket>()?;
+    m.add_class::<WillMsgReqPacket>()?;
+    m.add_class::<WillMsgPacket>()?;
+    m.add_class::<RegisterPacket>()?;
+    m.add_class::<RegAckPacket>()?;
+    m.add_class::<PublishPacket>()?;
+    m.add_class::<PubAckPacket>()?;
+    m.add_class::<PubCompPacket>()?;
+    m.add_class::<PubRecPacket>()?;
+    m.add_class::<PubRelPacket>()?;
+    m.add_class::<SubscribePacket>()?;
+    m.add_class::<SubAckPacket>()?;
+    m.add_class::<UnsubscribePacket>()?;
+    m.add_class::<UnsubAckPacket>()?;
+    m.add_class::<PingReqPacket>()?;
+    m.add_class::<PingRespPacket>()?;
+    m.add_class::<DisconnectPacket>()?;
+    m.add_class::<WillTopicUpdPacket>()?;
+    m.add_class::<WillTopicRespPacket>()?;
+    m.add_class::<WillMsgUpdPacket>()?;
+    m.add_class::<WillMsgRespPacket>()?;
+    // Functions
+    m.add_function(wrap_pyfunction!(read, &m)?)?;
+    parent.add("sn", &m)?;
+    // Register the submodule so that it can be imported as mqtt5.sn
+    py.import("sys")?
+        .getattr("modules")?
+        .set_item("mqtt5.sn", &m)?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::check_size::check_packet_size;
+use crate::enums::{PacketType, ProtocolVersion, QoS};
+use crate::errors::ProtocolError;
+use crate::io::{VariableByteInteger, Writable, WriteCursor, buffer_bytes};
+use crate::packets::{Framed, PublishPacket};
+use pyo3::PyResult;
+use pyo3::buffer::PyBuffer;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+#[pyclass(frozen, module = "mqtt5")]
+pub struct PublishTemplate {
+    qos: QoS,
+    retain: bool,
+    /// Encoded topic
+    topic: Vec<u8>,
+    /// Encoded properties, prefixed with their length
+    properties: Vec<u8>,
+}
+
+#[pymethods]
+impl PublishTemplate {
+    #[new]
+    #[pyo3(signature = (packet, *, version=ProtocolVersion::V5))]
+    pub fn new(packet: PyRef<'_, PublishPacket>, version: ProtocolVersion) -> PyResult<Self> {
+        // Write the header of the packet once and keep the parts that don't change
+        let py = packet.py();
+        let backing = packet.backing(py)?;
+        let frame = packet.frame(py, &backing)?;
+        let size = frame.size_with_payload(0, version)?;
+        let mut header = vec![0; size.nbytes()];
+        frame.write_header_to(&mut WriteCursor::new(&mut header, 0), size, version)?;
+        let topic_start = 1 + size.remaining_length.nbytes();
+        let topic_end = topic_start + frame.topic.nbytes();
+        let properties_start = topic_end + frame.packet_id.nbytes();
+        Ok(Self {
+            qos: packet.qos,
+            retain: packet.retain,
+            topic: header[topic_start..topic_end].to_vec(),
+            properties: header[properties_start..].to_vec(),
+        })
+    }
+
+    #[pyo3(signature = (payload, packet_id=None, duplicate=false, *, max_packet_size=None))]
+    pub fn encode(
+        &self,
+        py: Python,
+        payload: PyBuffer<u8>,
+        packet_id: Option<u16>,
+        duplicate: bool,
+        max_packet_size: Option<u32>,
+    ) -> PyResult<Py<PyBytes>> {
+        if packet_id.is_some() && self.qos == QoS::AtMostOnce {
+            return Err(ProtocolError::new_err(
+                "Packet ID must not be set for QoS=0",
+            ));
+        }
+        if packet_id.is_none() && (self.qos == QoS::AtLeastOnce || self.qos == QoS::ExactlyOnce) {
+            return Err(ProtocolError::new_err(
+                "Packet ID must be set for QoS=1 and QoS=2",
+            ));
+        }
+        let payload = buffer_bytes(&payload)?;
+        let remaining_length = VariableByteInteger::new(
+            self.topic.len() + packet_id.nbytes() + self.properties.len() + payload.len(),
+        )?;
+        let nbytes = 1 + remaining_length.nbytes() + usize::from(remaining_length);
+        check_packet_size(nbytes, max_packet_size)?;
+        PyBytes::new_with(py, nbytes, |buffer| {
+            let mut cursor = WriteCursor::new(buffer, 0);
+
+            // [3.3.1] Fixed header
+            let first_byte = (PacketType::Publish as u8) << 4
+                | (duplicate as u8) << 3
+                | (self.qos as u8) << 1
+                | self.retain as u8;
+            first_byte.write(&mut cursor);
+            remaining_length.write(&mut cursor);
+
+            // [3.3.2] Variable header
+            cursor.write_slice(&self.topic);
+            packet_id.write(&mut cursor);
+            cursor.write_slice(&self.properties);
+
+            // [3.3.3] Payload
+            cursor.write_slice(payload);
+
+            Ok(())
+        })
+        .map(|bytes| bytes.unbind())
+    }
+}
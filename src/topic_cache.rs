@@ -0,0 +1,63 @@
+use pyo3::PyResult;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Bounded cache that returns the same string object for repeated topics on read.
+///
+/// The cache is cleared when it's full, such that it follows topics that change over time.
+#[pyclass(frozen, module = "mqtt5")]
+pub struct TopicCache {
+    capacity: usize,
+    strings: Mutex<HashMap<Box<str>, Py<PyString>>>,
+}
+
+#[pymethods]
+impl TopicCache {
+    #[new]
+    #[pyo3(signature = (capacity=1024))]
+    pub fn new(capacity: usize) -> PyResult<Self> {
+        if capacity == 0 {
+            return Err(PyValueError::new_err("Capacity must be != 0"));
+        }
+        Ok(Self {
+            capacity,
+            strings: Mutex::new(HashMap::new()),
+        })
+    }
+
+    #[getter]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut strings) = self.strings.lock() {
+            strings.clear();
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.strings.lock().map_or(0, |strings| strings.len())
+    }
+}
+
+impl TopicCache {
+    /// Returns the cached string object for the value, creating and caching it if needed.
+    pub fn get(&self, py: Python, value: &str) -> Py<PyString> {
+        let Ok(mut strings) = self.strings.lock() else {
+            return PyString::new(py, value).unbind();
+        };
+        if let Some(string) = strings.get(value) {
+            return string.clone_ref(py);
+        }
+        if strings.len() >= self.capacity {
+            strings.clear();
+        }
+        let string = PyString::new(py, value).unbind();
+        strings.insert(value.into(), string.clone_ref(py));
+        string
+    }
+}
@@ -29,7 +29,7 @@ def _connect_packet_will() -> mqtt5.ConnectPacket:
             payload=b"\x12" * 2**8,
             qos=mqtt5.QoS.EXACTLY_ONCE,
             retain=True,
-            payload_format_indicator=1,
+            payload_format_indicator=mqtt5.PayloadFormatIndicator.UTF8,
             message_expiry_interval=2**24,
             content_type="text/html",
             response_topic="foo/bar/1234",
@@ -92,7 +92,7 @@ def _connect_packet_full() -> mqtt5.ConnectPacket:
             payload=b"\x12" * 2**8,
             qos=mqtt5.QoS.EXACTLY_ONCE,
             retain=True,
-            payload_format_indicator=1,
+            payload_format_indicator=mqtt5.PayloadFormatIndicator.UTF8,
             message_expiry_interval=2**24,
             content_type="text/html",
             response_topic="foo/bar/1234",
@@ -373,6 +373,30 @@ def _puback_packet_full_zmqtt() -> zmqtt._internal.packets.PubAck:
     )
 
 
+def _puback_packet_many() -> mqtt5.PubAckPacket:
+    return mqtt5.PubAckPacket(
+        packet_id=999,
+        user_properties=[(f"key{i}", f"value{i}") for i in range(32)],
+    )
+
+
+def _puback_packet_many_mqttproto() -> mqttproto.MQTTPublishAckPacket:
+    return mqttproto.MQTTPublishAckPacket(
+        packet_id=999,
+        reason_code=mqttproto.ReasonCode.SUCCESS,
+        user_properties={f"key{i}": f"value{i}" for i in range(32)},
+    )
+
+
+def _puback_packet_many_zmqtt() -> zmqtt._internal.packets.PubAck:
+    return zmqtt._internal.packets.PubAck(
+        packet_id=999,
+        properties=zmqtt._internal.packets.PubAckProperties(
+            user_properties=tuple((f"key{i}", f"value{i}") for i in range(32)),
+        ),
+    )
+
+
 def _pubrec_packet() -> mqtt5.PubRecPacket:
     return mqtt5.PubRecPacket(packet_id=999)
 
@@ -542,6 +566,36 @@ def _subscribe_packet_zmqtt() -> zmqtt._internal.packets.Subscribe:
     )
 
 
+def _subscribe_packet_many() -> mqtt5.SubscribePacket:
+    return mqtt5.SubscribePacket(
+        packet_id=999,
+        topic_filters=[mqtt5.TopicFilter(pattern=f"+/bar/{i}/#") for i in range(32)],
+    )
+
+
+def _subscribe_packet_many_mqttproto() -> mqttproto.MQTTSubscribePacket:
+    return mqttproto.MQTTSubscribePacket(
+        packet_id=999,
+        subscriptions=[
+            mqttproto.Subscription(pattern=f"+/bar/{i}/#") for i in range(32)
+        ],
+    )
+
+
+def _subscribe_packet_many_zmqtt() -> zmqtt._internal.packets.Subscribe:
+    return zmqtt._internal.packets.Subscribe(
+        packet_id=999,
+        subscriptions=tuple(
+            zmqtt._internal.packets.SubscriptionRequest(
+                topic_filter=f"+/bar/{i}/#",
+                qos=zmqtt.QoS.EXACTLY_ONCE,
+                retain_as_published=True,
+            )
+            for i in range(32)
+        ),
+    )
+
+
 def _suback_packet() -> mqtt5.SubAckPacket:
     return mqtt5.SubAckPacket(
         packet_id=999, reason_codes=[mqtt5.SubAckReasonCode.TOPIC_FILTER_INVALID]
@@ -1,6 +1,8 @@
 """Tests error handling in edge cases."""
 
+import inspect
 import re
+import typing
 
 import conftest
 import pytest
@@ -13,10 +15,159 @@ def test_read_incomplete_buffer(packet: mqtt5.Packet) -> None:
     """Test error from reading an incomplete buffer."""
     buffer = memoryview(packet.write())
     for index in range(len(buffer)):
-        with pytest.raises(IndexError):
+        with pytest.raises(mqtt5.IncompleteError):
             mqtt5.read(buffer[:index])
 
 
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_decoder_incomplete_buffer(packet: mqtt5.Packet) -> None:
+    """Test that the decoder waits for more bytes instead of raising."""
+    data = packet.write()
+    decoder = mqtt5.Decoder()
+    decoder.feed(data[:-1])
+    assert list(decoder) == []
+    decoder.feed(data[-1:])
+    assert list(decoder) == [packet]
+
+
+def test_decoder_skip_malformed_packet() -> None:
+    """Test that the decoder skips a malformed packet after raising."""
+    decoder = mqtt5.Decoder()
+    decoder.feed(b"\x60\x04\xff\xff\x00\x00\xd0\x00")
+    with pytest.raises(ValueError, match="^Invalid fixed header flags$"):
+        next(decoder)
+    assert list(decoder) == [mqtt5.PingRespPacket()]
+
+
+@pytest.mark.parametrize(
+    ("buffer", "message"),
+    [
+        pytest.param(
+            b"\x00\x02",
+            "Invalid PacketType value: 0",
+            id="Invalid packet type",
+        ),
+        pytest.param(
+            b"\x20\x80\x80\x80\x80",
+            "Invalid variable byte integer",
+            id="VariableByteInteger 4th continuation bit",
+        ),
+    ],
+)
+def test_peek_header_malformed_bytes(buffer: bytes, message: str) -> None:
+    """Test error from peeking at a malformed fixed header."""
+    with pytest.raises(mqtt5.MalformedPacketError, match=f"^{re.escape(message)}$"):
+        mqtt5.peek_header(memoryview(buffer))
+
+
+@pytest.mark.parametrize(
+    ("buffer", "error", "message"),
+    [
+        pytest.param(
+            b"\x20\x02\x00\x00",
+            mqtt5.ProtocolError,
+            "Packet is not a connect packet",
+            id="ConnAck",
+        ),
+        pytest.param(
+            b"\x11\x0d\x00\x04MQTT\x04",
+            mqtt5.MalformedPacketError,
+            "Invalid fixed header flags",
+            id="Invalid fixed header flags",
+        ),
+        pytest.param(
+            b"\x10\x0c\x00\x03SSH\x05",
+            mqtt5.ProtocolError,
+            "Invalid protocol name",
+            id="Invalid protocol name",
+        ),
+        pytest.param(
+            b"\x10\x0d\x00\x04MQ",
+            mqtt5.IncompleteError,
+            "Not enough bytes",
+            id="Incomplete protocol name",
+        ),
+        pytest.param(
+            b"\x10\x02\x00\x04MQTT\x04",
+            mqtt5.MalformedPacketError,
+            "Invalid remaining length",
+            id="Remaining length too short",
+        ),
+    ],
+)
+def test_sniff_connect_malformed_bytes(
+    buffer: bytes, error: type[mqtt5.MQTTError], message: str
+) -> None:
+    """Test error from sniffing the protocol of a malformed connect packet."""
+    with pytest.raises(error, match=f"^{re.escape(message)}$"):
+        mqtt5.sniff_connect(memoryview(buffer))
+
+
+@pytest.mark.parametrize(
+    ("buffer", "protocol_name", "protocol_level", "version", "connack"),
+    [
+        pytest.param(
+            b"\x10\x0d\x00\x04MQTT\x06\x02\x00\x3c\x00\x01a",
+            "MQTT",
+            6,
+            4,
+            b"\x20\x02\x00\x01",
+            id="MQTT 6",
+        ),
+        pytest.param(
+            b"\x10\x0d\x00\x04MQTT\x03\x02\x00\x3c\x00\x01a",
+            "MQTT",
+            3,
+            4,
+            b"\x20\x02\x00\x01",
+            id="MQTT 3",
+        ),
+        pytest.param(
+            b"\x10\x0f\x00\x06MQIsdp\x04\x02\x00\x3c\x00\x01a",
+            "MQIsdp",
+            4,
+            3,
+            b"\x20\x02\x00\x01",
+            id="MQIsdp 4",
+        ),
+    ],
+)
+def test_sniff_connect_unsupported_version(
+    buffer: bytes,
+    protocol_name: str,
+    protocol_level: int,
+    version: typing.Literal[3, 4, 5],
+    connack: bytes,
+) -> None:
+    """Test that the error tells how to reject an unsupported protocol version."""
+    with pytest.raises(
+        mqtt5.UnsupportedProtocolVersionError, match="^Invalid protocol version$"
+    ) as info:
+        mqtt5.sniff_connect(memoryview(buffer))
+    assert info.value.protocol_name == protocol_name
+    assert info.value.protocol_level == protocol_level
+    assert info.value.version == version
+    assert info.value.packet_type == mqtt5.PacketType.CONNECT
+    packet = mqtt5.ConnAckPacket(
+        reason_code=mqtt5.ConnAckReasonCode.UNSUPPORTED_PROTOCOL_VERSION
+    )
+    assert packet.write(version=info.value.version) == connack
+
+
+def test_read_unsupported_version() -> None:
+    """Test error from reading a connect packet of another protocol version."""
+    data = mqtt5.ConnectPacket(client_id="Bulbasaur").write()
+    with pytest.raises(mqtt5.UnsupportedProtocolVersionError) as info:
+        mqtt5.read(memoryview(data), version=4)
+    assert (info.value.protocol_name, info.value.protocol_level) == ("MQTT", 5)
+    assert info.value.version == 5
+    decoder = mqtt5.Decoder(version=3)
+    decoder.feed(data)
+    with pytest.raises(mqtt5.UnsupportedProtocolVersionError) as info:
+        next(decoder)
+    assert (info.value.protocol_name, info.value.protocol_level) == ("MQTT", 5)
+
+
 @pytest.mark.parametrize(
     ("buffer", "message"),
     [
@@ -140,11 +291,106 @@ def test_read_incomplete_buffer(packet: mqtt5.Packet) -> None:
             "Invalid remaining length",
             id="PubAck: Remaining length value too small",
         ),
+        pytest.param(
+            b"\x30\x06\x00\x01\x61\x02\x01\x02",
+            "Invalid PayloadFormatIndicator value: 2",
+            id="Publish: Invalid payload format indicator",
+        ),
         pytest.param(
             b"\x30\x03\x00\x00\x00",
             "Topic alias must be set if topic is empty",
             id="Publish: Empty topic without topic alias",
         ),
+        pytest.param(
+            b"\x30\x05\x00\x02\xc3\x28\x00",
+            "Invalid UTF-8 string",
+            id="Publish: Topic with invalid UTF-8",
+        ),
+        pytest.param(
+            b"\x30\x05\x00\x02\xed\xa0\x80",
+            "Invalid UTF-8 string",
+            id="Publish: Topic with surrogate code point",
+        ),
+        pytest.param(
+            b"\x30\x06\x00\x03\x61\x00\x62\x00",
+            "Invalid character in string",
+            id="Publish: Topic with null character",
+        ),
+        pytest.param(
+            b"\x40\x0b\x00\x01\x00\x07\x26\x00\x01\x61\x00\x01\xff",
+            "Invalid UTF-8 string",
+            id="PubAck: User property with invalid UTF-8",
+        ),
+        pytest.param(
+            b"\x10\x15\x00\x04\x4d\x51\x54\x54\x05\x04\x00\x00\x00\x00\x00\x00\x00\x03\x61\x2f\x23\x00\x00",
+            "Invalid topic",
+            id="Connect: Will topic with multi-level wildcard",
+        ),
+        pytest.param(
+            b"\x30\x06\x00\x03\x61\x2f\x2b\x00",
+            "Invalid topic",
+            id="Publish: Topic with single-level wildcard",
+        ),
+        pytest.param(
+            b"\x30\x06\x00\x03\x61\x2f\x23\x00",
+            "Invalid topic",
+            id="Publish: Topic with multi-level wildcard",
+        ),
+        pytest.param(
+            b"\x30\x0a\x00\x01\x61\x06\x08\x00\x03\x61\x2f\x2b",
+            "Invalid topic",
+            id="Publish: Response topic with single-level wildcard",
+        ),
+        pytest.param(
+            b"\x82\x03\x00\x01\x00",
+            "Topic filter list must contain at least one entry",
+            id="Subscribe: Empty topic filter list",
+        ),
+        pytest.param(
+            b"\x82\x09\x00\x01\x00\x00\x03\x61\x2b\x62\x0a",
+            "Invalid topic filter",
+            id="Subscribe: Single-level wildcard not alone in level",
+        ),
+        pytest.param(
+            b"\xa2\x03\x00\x01\x00",
+            "Pattern list must contain at least one entry",
+            id="Unsubscribe: Empty pattern list",
+        ),
+        pytest.param(
+            b"\xa2\x0a\x00\x01\x00\x00\x05\x61\x2f\x23\x2f\x62",
+            "Invalid topic filter",
+            id="Unsubscribe: Multi-level wildcard in the middle",
+        ),
+        pytest.param(
+            b"\x10\x0d\x00\x04\x4d\x51\x54\x54\x05\x01\x00\x00\x00\x00\x00",
+            "Invalid connect flags",
+            id="Connect: Reserved flag set",
+        ),
+        pytest.param(
+            b"\x10\x0d\x00\x04\x4d\x51\x54\x54\x05\x08\x00\x00\x00\x00\x00",
+            "Invalid connect flags",
+            id="Connect: Will QoS without will",
+        ),
+        pytest.param(
+            b"\x10\x0d\x00\x04\x4d\x51\x54\x54\x05\x20\x00\x00\x00\x00\x00",
+            "Invalid connect flags",
+            id="Connect: Will retain without will",
+        ),
+        pytest.param(
+            b"\x82\x07\x00\x01\x00\x00\x01\x61\x42",
+            "Invalid subscription options",
+            id="Subscribe: Reserved option bit 6 set",
+        ),
+        pytest.param(
+            b"\x82\x07\x00\x01\x00\x00\x01\x61\x82",
+            "Invalid subscription options",
+            id="Subscribe: Reserved option bit 7 set",
+        ),
+        pytest.param(
+            b"\x82\x07\x00\x01\x00\x00\x01\x61\x32",
+            "Invalid subscription options",
+            id="Subscribe: Retain handling == 3",
+        ),
     ],
 )
 def test_read_malformed_bytes(buffer: bytearray, message: str) -> None:
@@ -153,6 +399,432 @@ def test_read_malformed_bytes(buffer: bytearray, message: str) -> None:
         mqtt5.read(memoryview(buffer))
 
 
+@pytest.mark.parametrize(
+    ("buffer", "message"),
+    [
+        pytest.param(
+            b"\x10\x0d\x00\x04MQTT\x05\x02\x00\x3c\x00\x01a",
+            "Invalid protocol version",
+            id="Connect: Protocol version 5",
+        ),
+        pytest.param(
+            b"\x10\x0d\x00\x04MQTT\x04\x42\x00\x3c\x00\x01a",
+            "Invalid connect flags",
+            id="Connect: Password without username",
+        ),
+        pytest.param(
+            b"\x20\x02\x00\x06",
+            "Invalid ConnAck return code: 6",
+            id="ConnAck: Return code 6",
+        ),
+        pytest.param(
+            b"\x40\x03\x00\x01\x00",
+            "Invalid remaining length",
+            id="PubAck: Reason code",
+        ),
+        pytest.param(
+            b"\x82\x06\x00\x01\x00\x01a\x04",
+            "Invalid subscription options",
+            id="Subscribe: No local",
+        ),
+        pytest.param(
+            b"\x90\x03\x00\x01\x83",
+            "Invalid SubAck return code: 131",
+            id="SubAck: Implementation specific error",
+        ),
+        pytest.param(
+            b"\xf0\x00",
+            "Auth packet is not supported before MQTT 5",
+            id="Auth",
+        ),
+    ],
+)
+def test_read_malformed_bytes_v311(buffer: bytes, message: str) -> None:
+    """Test error from reading bytes that aren't valid MQTT 3.1.1."""
+    with pytest.raises(ValueError, match=f"^{re.escape(message)}$"):
+        mqtt5.read(memoryview(buffer), version=4)
+
+
+@pytest.mark.parametrize(
+    ("packet", "message"),
+    [
+        pytest.param(
+            mqtt5.ConnectPacket(client_id="Bulbasaur", session_expiry_interval=1),
+            "Properties are not supported before MQTT 5",
+            id="Connect: Session expiry interval",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(
+                client_id="Bulbasaur",
+                will=mqtt5.Will(topic="foo", will_delay_interval=1),
+            ),
+            "Properties are not supported before MQTT 5",
+            id="Connect: Will delay interval",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(client_id="Bulbasaur", password=b"foo"),
+            "Password without username is not supported before MQTT 5",
+            id="Connect: Password without username",
+        ),
+        pytest.param(
+            mqtt5.ConnAckPacket(reason_code=mqtt5.ConnAckReasonCode.BANNED),
+            "Reason code is not supported before MQTT 5",
+            id="ConnAck: Banned",
+        ),
+        pytest.param(
+            mqtt5.PublishPacket(topic="foo", payload=b"", user_properties=[("a", "b")]),
+            "Properties are not supported before MQTT 5",
+            id="Publish: User properties",
+        ),
+        pytest.param(
+            mqtt5.PubAckPacket(
+                packet_id=1, reason_code=mqtt5.PubAckReasonCode.NOT_AUTHORIZED
+            ),
+            "Reason code is not supported before MQTT 5",
+            id="PubAck: Not authorized",
+        ),
+        pytest.param(
+            mqtt5.SubscribePacket(
+                packet_id=1,
+                topic_filters=[mqtt5.TopicFilter(pattern="foo", no_local=True)],
+            ),
+            "Subscription options are not supported before MQTT 5",
+            id="Subscribe: No local",
+        ),
+        pytest.param(
+            mqtt5.SubAckPacket(
+                packet_id=1,
+                reason_codes=[mqtt5.SubAckReasonCode.TOPIC_FILTER_INVALID],
+            ),
+            "Reason code is not supported before MQTT 5",
+            id="SubAck: Topic filter invalid",
+        ),
+        pytest.param(
+            mqtt5.UnsubAckPacket(
+                packet_id=1, reason_codes=[mqtt5.UnsubAckReasonCode.SUCCESS]
+            ),
+            "Reason codes are not supported before MQTT 5",
+            id="UnsubAck: Reason codes",
+        ),
+        pytest.param(
+            mqtt5.DisconnectPacket(
+                reason_code=mqtt5.DisconnectReasonCode.SERVER_SHUTTING_DOWN
+            ),
+            "Reason code is not supported before MQTT 5",
+            id="Disconnect: Server shutting down",
+        ),
+        pytest.param(
+            mqtt5.AuthPacket(),
+            "Auth packet is not supported before MQTT 5",
+            id="Auth",
+        ),
+    ],
+)
+def test_write_unsupported_v311(packet: mqtt5.Packet, message: str) -> None:
+    """Test error from writing values that MQTT 3.1.1 can't express."""
+    with pytest.raises(mqtt5.ProtocolError, match=f"^{re.escape(message)}$"):
+        packet.write(version=4)
+
+
+@pytest.mark.parametrize(
+    ("buffer", "message"),
+    [
+        pytest.param(
+            b"\x10\x0d\x00\x04MQTT\x03\x02\x00\x3c\x00\x01a",
+            "Invalid protocol version",
+            id="Connect: MQTT 3.1.1 protocol name",
+        ),
+        pytest.param(
+            b"\x10\x0e\x00\x06MQIsdp\x03\x02\x00\x3c\x00\x00",
+            "Client id must have 1 to 23 characters in MQTT 3.1",
+            id="Connect: Empty client id",
+        ),
+        pytest.param(
+            b"\x10\x26\x00\x06MQIsdp\x03\x02\x00\x3c\x00\x18" + b"a" * 24,
+            "Client id must have 1 to 23 characters in MQTT 3.1",
+            id="Connect: Client id too long",
+        ),
+        pytest.param(
+            b"\x20\x02\x01\x00",
+            "Invalid acknowledge flags",
+            id="ConnAck: Session present",
+        ),
+    ],
+)
+def test_read_malformed_bytes_v31(buffer: bytes, message: str) -> None:
+    """Test error from reading bytes that aren't valid MQTT 3.1."""
+    with pytest.raises(ValueError, match=f"^{re.escape(message)}$"):
+        mqtt5.read(memoryview(buffer), version=3)
+
+
+@pytest.mark.parametrize(
+    ("packet", "message"),
+    [
+        pytest.param(
+            mqtt5.ConnectPacket(client_id=""),
+            "Client id must have 1 to 23 characters in MQTT 3.1",
+            id="Connect: Empty client id",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(client_id="a" * 24),
+            "Client id must have 1 to 23 characters in MQTT 3.1",
+            id="Connect: Client id too long",
+        ),
+        pytest.param(
+            mqtt5.ConnAckPacket(session_present=True),
+            "Session present is not supported before MQTT 3.1.1",
+            id="ConnAck: Session present",
+        ),
+    ],
+)
+def test_write_unsupported_v31(packet: mqtt5.Packet, message: str) -> None:
+    """Test error from writing values that MQTT 3.1 can't express."""
+    with pytest.raises(mqtt5.ProtocolError, match=f"^{re.escape(message)}$"):
+        packet.write(version=3)
+
+
+@pytest.mark.parametrize(
+    ("packet", "topic_aliases", "error", "message"),
+    [
+        pytest.param(
+            mqtt5.PublishPacket(topic="", payload=b"", topic_alias=1),
+            None,
+            ValueError,
+            "Unknown topic alias: 1",
+            id="Publish: Without topic aliases",
+        ),
+        pytest.param(
+            mqtt5.PublishPacket(topic="", payload=b"", topic_alias=2),
+            {1: "foo"},
+            ValueError,
+            "Unknown topic alias: 2",
+            id="Publish: Unknown topic alias",
+        ),
+        pytest.param(
+            mqtt5.AuthPacket(),
+            None,
+            TypeError,
+            "Auth packet is not supported before MQTT 5",
+            id="Auth",
+        ),
+    ],
+)
+def test_downgrade_unsupported(
+    packet: mqtt5.Packet,
+    topic_aliases: dict[int, str] | None,
+    error: type[Exception],
+    message: str,
+) -> None:
+    """Test error from downgrading packets that MQTT 3.1.1 can't express."""
+    with pytest.raises(error, match=f"^{re.escape(message)}$") as info:
+        mqtt5.downgrade(packet, topic_aliases=topic_aliases)
+    # Bad arguments aren't protocol errors of the peer
+    assert not isinstance(info.value, mqtt5.MQTTError)
+
+
+def test_invalid_version() -> None:
+    """Test error from reading or writing with an unknown protocol version."""
+    with pytest.raises(ValueError, match="^Invalid protocol version: 6$") as info:
+        mqtt5.PingReqPacket().write(version=6)
+    assert not isinstance(info.value, mqtt5.MQTTError)
+    with pytest.raises(ValueError, match="^Invalid protocol version: 6$") as info:
+        mqtt5.Decoder(version=6)
+    assert not isinstance(info.value, mqtt5.MQTTError)
+
+
+@pytest.mark.parametrize(
+    ("buffer", "error", "packet_type", "offset"),
+    [
+        pytest.param(
+            b"\x00\x02\x00\x00",
+            mqtt5.MalformedPacketError,
+            None,
+            2,
+            id="Invalid packet type",
+        ),
+        pytest.param(
+            b"\x20\x83\x80\x80\x80\x00\x00\x00",
+            mqtt5.MalformedPacketError,
+            None,
+            5,
+            id="Invalid remaining length",
+        ),
+        pytest.param(
+            b"\x30\x07\x00\x01\x61\x03\x23\x00\x00",
+            mqtt5.ProtocolError,
+            mqtt5.PacketType.PUBLISH,
+            None,
+            id="Publish: Topic alias == 0",
+        ),
+        pytest.param(
+            b"\x10\x11\x00\x04MQTT\x05\x00\x00\x00\x03\x21\x00\x00\x00\x01a",
+            mqtt5.ProtocolError,
+            mqtt5.PacketType.CONNECT,
+            None,
+            id="Connect: Receive maximum == 0",
+        ),
+        pytest.param(
+            b"\x20\x09\x00\x00\x06\x11\x00\x00\x00\x00\x11",
+            mqtt5.ProtocolError,
+            mqtt5.PacketType.CONN_ACK,
+            11,
+            id="ConnAck: Duplicate property",
+        ),
+        pytest.param(
+            b"\x40\x04\x00\x01\x00\x05",
+            mqtt5.MalformedPacketError,
+            mqtt5.PacketType.PUB_ACK,
+            6,
+            id="PubAck: Property length exceeds remaining length",
+        ),
+    ],
+)
+def test_read_error_details(
+    buffer: bytes,
+    error: type[mqtt5.MQTTError],
+    packet_type: mqtt5.PacketType | None,
+    offset: int | None,
+) -> None:
+    """Test that errors carry the reason code, packet type, and offset."""
+    with pytest.raises(error) as info:
+        mqtt5.read(memoryview(buffer))
+    assert info.value.packet_type == packet_type
+    assert info.value.offset == offset
+    decoder = mqtt5.Decoder()
+    decoder.feed(buffer)
+    with pytest.raises(error) as info:
+        next(decoder)
+    assert info.value.packet_type == packet_type
+    assert info.value.offset == offset
+
+
+def test_error_reason_codes() -> None:
+    """Test that the error classes map to the corresponding reason codes."""
+    assert issubclass(mqtt5.MQTTError, ValueError)
+    assert (
+        mqtt5.MalformedPacketError.reason_code
+        == mqtt5.DisconnectReasonCode.MALFORMED_PACKET
+    )
+    assert mqtt5.ProtocolError.reason_code == mqtt5.DisconnectReasonCode.PROTOCOL_ERROR
+    assert issubclass(mqtt5.UnsupportedProtocolVersionError, mqtt5.ProtocolError)
+    with pytest.raises(mqtt5.ProtocolError) as info:
+        mqtt5.ConnectPacket(client_id="Bulbasaur", receive_max=0)
+    assert info.value.reason_code == mqtt5.DisconnectReasonCode.PROTOCOL_ERROR
+    assert info.value.packet_type is None
+    assert info.value.offset is None
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_read_max_packet_size(packet: mqtt5.Packet) -> None:
+    """Test that packets larger than the maximum packet size are rejected."""
+    data = packet.write()
+    _, _, _, header_nbytes = mqtt5.peek_header(memoryview(data))
+    assert mqtt5.read(memoryview(data), max_packet_size=len(data))[0] == packet
+    # The fixed header alone is enough to reject the packet
+    for buffer in (data, data[:header_nbytes]):
+        with pytest.raises(mqtt5.PacketTooLargeError) as info:
+            mqtt5.read(memoryview(buffer), max_packet_size=len(data) - 1)
+        assert info.value.reason_code == mqtt5.DisconnectReasonCode.PACKET_TOO_LARGE
+        assert info.value.packet_type == data[0] >> 4
+        assert info.value.offset == header_nbytes
+        decoder = mqtt5.Decoder(max_packet_size=len(data) - 1)
+        decoder.feed(buffer)
+        with pytest.raises(mqtt5.PacketTooLargeError):
+            next(decoder)
+
+
+def test_read_max_packet_size_zero() -> None:
+    """Test that a maximum packet size of zero is rejected."""
+    message = "Maximum packet size must be != 0"
+    with pytest.raises(ValueError, match=f"^{re.escape(message)}$") as info:
+        mqtt5.read(memoryview(b"\xd0\x00"), max_packet_size=0)
+    assert not isinstance(info.value, mqtt5.MQTTError)
+    with pytest.raises(ValueError, match=f"^{re.escape(message)}$") as info:
+        mqtt5.Decoder(max_packet_size=0)
+    assert not isinstance(info.value, mqtt5.MQTTError)
+
+
+@pytest.mark.parametrize(
+    "packet",
+    [
+        pytest.param(
+            mqtt5.PublishPacket(
+                topic="foo",
+                payload=b"\xc3\x28",
+                payload_format_indicator=mqtt5.PayloadFormatIndicator.UTF8,
+            ),
+            id="Publish",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(
+                client_id="Bulbasaur",
+                will=mqtt5.Will(
+                    topic="foo",
+                    payload=b"\xc3\x28",
+                    payload_format_indicator=mqtt5.PayloadFormatIndicator.UTF8,
+                ),
+            ),
+            id="Connect: Will",
+        ),
+    ],
+)
+def test_read_check_payload_format(packet: mqtt5.Packet) -> None:
+    """Test that the payload format check rejects UTF-8 payloads with invalid bytes."""
+    data = packet.write()
+    assert mqtt5.read(memoryview(data))[0] == packet
+    message = "Invalid UTF-8 payload"
+    with pytest.raises(mqtt5.PayloadFormatInvalidError, match=f"^{message}$") as info:
+        mqtt5.read(memoryview(data), check_payload_format=True)
+    assert info.value.reason_code == mqtt5.DisconnectReasonCode.PAYLOAD_FORMAT_INVALID
+    decoder = mqtt5.Decoder(check_payload_format=True)
+    decoder.feed(data)
+    with pytest.raises(mqtt5.PayloadFormatInvalidError, match=f"^{message}$"):
+        next(decoder)
+
+
+@pytest.mark.parametrize(
+    "buffer",
+    [
+        pytest.param(b"\x30\x06\x00\x03\x61\x01\x62\x00", id="Control character"),
+        pytest.param(b"\x30\x06\x00\x03\x61\xc2\x9f\x00", id="C1 control character"),
+        pytest.param(b"\x30\x06\x00\x03\xef\xbf\xbf\x00", id="Non-character"),
+    ],
+)
+def test_read_strict_strings(buffer: bytes) -> None:
+    """Test that strict string validation rejects discouraged characters."""
+    mqtt5.read(memoryview(buffer))
+    with pytest.raises(ValueError, match="^Invalid character in string$"):
+        mqtt5.read(memoryview(buffer), strict_strings=True)
+    decoder = mqtt5.Decoder(strict_strings=True)
+    decoder.feed(buffer)
+    with pytest.raises(ValueError, match="^Invalid character in string$"):
+        next(decoder)
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_write_max_packet_size(packet: mqtt5.Packet) -> None:
+    """Test that packets that don't fit into the maximum packet size are rejected."""
+    data = packet.write()
+    assert packet.write(max_packet_size=len(data)) == data
+    # Packets with a reason string can drop it and their user properties to fit
+    cls = type(packet)
+    args = {name: getattr(packet, name) for name in inspect.signature(cls).parameters}
+    if "reason_str" in args:
+        args.update(reason_str=None, user_properties=None)
+    max_packet_size = len(cls(**args).write()) - 1
+    with pytest.raises(mqtt5.PacketTooLargeError) as info:
+        packet.write(max_packet_size=max_packet_size)
+    assert info.value.reason_code == mqtt5.DisconnectReasonCode.PACKET_TOO_LARGE
+
+
+def test_write_remaining_length_overflow() -> None:
+    """Test that packets beyond the largest remaining length raise instead of panicking."""
+    packet = mqtt5.PublishPacket(topic="foo", payload=bytes(2**28))
+    message = "Remaining length must be < 2**28"
+    with pytest.raises(mqtt5.PacketTooLargeError, match=f"^{re.escape(message)}$"):
+        packet.write()
+
+
 @pytest.mark.parametrize(
     ("cls", "args", "message"),
     [
@@ -204,6 +876,12 @@ def test_read_malformed_bytes(buffer: bytearray, message: str) -> None:
             "Binary data must be < 65535 bytes",
             id="Connect: Password > 65535 bytes",
         ),
+        pytest.param(
+            mqtt5.ConnectPacket,
+            {"client_id": "Bulbasaur", "password": bytearray(65536)},
+            "Binary data must be < 65535 bytes",
+            id="Connect: Password buffer > 65535 bytes",
+        ),
         pytest.param(
             mqtt5.ConnectPacket,
             {"client_id": "Bulbasaur", "receive_max": 0},
@@ -368,3 +1046,407 @@ def test_invalid_arguments(cls: type, args: dict, message: str) -> None:
     """Test error from initializing with invalid arguments."""
     with pytest.raises(ValueError, match=f"^{re.escape(message)}$"):
         cls(**args)
+
+
+@pytest.mark.parametrize(
+    "make",
+    [
+        pytest.param(
+            lambda s: mqtt5.ConnectPacket(client_id=s),
+            id="Connect: Client id",
+        ),
+        pytest.param(
+            lambda s: mqtt5.ConnectPacket(client_id="a", username=s),
+            id="Connect: Username",
+        ),
+        pytest.param(
+            lambda s: mqtt5.ConnectPacket(client_id="a", authentication_method=s),
+            id="Connect: Authentication method",
+        ),
+        pytest.param(
+            lambda s: mqtt5.ConnectPacket(client_id="a", will=mqtt5.Will(topic=s)),
+            id="Connect: Will topic",
+        ),
+        pytest.param(
+            lambda s: mqtt5.ConnAckPacket(assigned_client_id=s),
+            id="ConnAck: Assigned client id",
+        ),
+        pytest.param(
+            lambda s: mqtt5.ConnAckPacket(response_info=s),
+            id="ConnAck: Response info",
+        ),
+        pytest.param(
+            lambda s: mqtt5.ConnAckPacket(server_reference=s),
+            id="ConnAck: Server reference",
+        ),
+        pytest.param(
+            lambda s: mqtt5.PublishPacket(topic=s, payload=b""),
+            id="Publish: Topic",
+        ),
+        pytest.param(
+            lambda s: mqtt5.PublishPacket(topic="a", payload=b"", content_type=s),
+            id="Publish: Content type",
+        ),
+        pytest.param(
+            lambda s: mqtt5.PublishPacket(topic="a", payload=b"", response_topic=s),
+            id="Publish: Response topic",
+        ),
+        pytest.param(
+            lambda s: mqtt5.PublishPacket(
+                topic="a", payload=b"", user_properties=[(s, "b")]
+            ),
+            id="Publish: User property key",
+        ),
+        pytest.param(
+            lambda s: mqtt5.PublishPacket(
+                topic="a", payload=b"", user_properties=[("b", s)]
+            ),
+            id="Publish: User property value",
+        ),
+        pytest.param(
+            lambda s: mqtt5.PubAckPacket(packet_id=1, reason_str=s),
+            id="PubAck: Reason string",
+        ),
+        pytest.param(
+            lambda s: mqtt5.SubscribePacket(
+                packet_id=1, topic_filters=[mqtt5.TopicFilter(pattern=s)]
+            ),
+            id="Subscribe: Pattern",
+        ),
+        pytest.param(
+            lambda s: mqtt5.UnsubscribePacket(packet_id=1, patterns=[s]),
+            id="Unsubscribe: Pattern",
+        ),
+        pytest.param(
+            lambda s: mqtt5.DisconnectPacket(reason_str=s),
+            id="Disconnect: Reason string",
+        ),
+        pytest.param(
+            lambda s: mqtt5.AuthPacket(authentication_method=s),
+            id="Auth: Authentication method",
+        ),
+    ],
+)
+def test_null_character_parity(make: typing.Callable[[str], mqtt5.Packet]) -> None:
+    """Test that constructors reject U+0000 in strings like read does."""
+    data = make("\x7f\x7f").write()
+    assert data.count(b"\x7f\x7f") == 1
+    message = "^Invalid character in string$"
+    with pytest.raises(mqtt5.MalformedPacketError, match=message):
+        make("\x7f\x00")
+    with pytest.raises(mqtt5.MalformedPacketError, match=message):
+        mqtt5.read(memoryview(data.replace(b"\x7f\x7f", b"\x7f\x00")))
+
+
+@pytest.mark.parametrize(
+    ("buffer", "offset", "message"),
+    [
+        (bytearray(1), 0, "Buffer is too small"),
+        (bytearray(4), 3, "Buffer is too small"),
+        (bytes(4), 0, "Buffer must be writable"),
+    ],
+)
+def test_write_into_invalid_buffer(buffer: bytes | bytearray, offset: int, message: str) -> None:
+    """Test that writing into a buffer without enough writable space raises."""
+    with pytest.raises(BufferError, match=f"^{message}$"):
+        mqtt5.PingReqPacket().write_into(buffer, offset)
+
+
+@pytest.mark.parametrize(
+    ("args", "message"),
+    [
+        ({"payload": memoryview(bytes(4))[::2]}, "Buffer must be contiguous"),
+        (
+            {"payload": b"", "correlation_data": memoryview(bytes(4))[::2]},
+            "Buffer must be contiguous",
+        ),
+    ],
+)
+def test_invalid_buffer_arguments(args: dict, message: str) -> None:
+    """Test that binary arguments must be contiguous buffers."""
+    with pytest.raises(BufferError, match=f"^{message}$"):
+        mqtt5.PublishPacket(topic="foo", **args)
+
+
+def test_write_many_invalid_packet() -> None:
+    """Test that writing many packets rejects objects that aren't packets."""
+    with pytest.raises(TypeError, match="^Expected a packet, got str$"):
+        mqtt5.write_many([mqtt5.PingReqPacket(), "foo"])
+
+
+def test_convert_invalid_packet() -> None:
+    """Test that converting rejects objects that aren't packets."""
+    with pytest.raises(TypeError, match="^Expected a packet, got str$"):
+        mqtt5.upgrade("foo")
+    with pytest.raises(TypeError, match="^Expected a packet, got str$"):
+        mqtt5.downgrade("foo")
+
+
+def test_topic_cache_capacity() -> None:
+    """Test that a topic cache can't be created without capacity."""
+    with pytest.raises(ValueError, match="^Capacity must be != 0$"):
+        mqtt5.TopicCache(capacity=0)
+
+
+@pytest.mark.parametrize(
+    "packet",
+    [
+        mqtt5.PubAckPacket(packet_id=1),
+        mqtt5.SubscribePacket(packet_id=1, topic_filters=[mqtt5.TopicFilter("foo")]),
+    ],
+)
+def test_write_invalid_list_item(packet: mqtt5.Packet) -> None:
+    """Test that writing rejects list items that were appended after initialization."""
+    packet.user_properties.append(("foo", 1))
+    with pytest.raises(TypeError):
+        packet.write()
+
+
+@pytest.mark.parametrize(
+    ("qos", "packet_id", "message"),
+    [
+        (mqtt5.QoS.AT_MOST_ONCE, 1, "Packet ID must not be set for QoS=0"),
+        (mqtt5.QoS.AT_LEAST_ONCE, None, "Packet ID must be set for QoS=1 and QoS=2"),
+    ],
+)
+def test_publish_template_packet_id(
+    qos: mqtt5.QoS, packet_id: int | None, message: str
+) -> None:
+    """Test that templates reject packet IDs that don't match the QoS."""
+    packet = mqtt5.PublishPacket(
+        topic="foo", payload=b"", qos=qos, packet_id=None if qos == 0 else 1
+    )
+    template = mqtt5.PublishTemplate(packet)
+    with pytest.raises(mqtt5.ProtocolError, match=f"^{message}$"):
+        template.encode(b"bar", packet_id)
+
+
+@pytest.mark.parametrize(
+    ("buffer", "error", "message"),
+    [
+        pytest.param(b"\x00\x16", mqtt5.MalformedPacketError, "Invalid length", id="Zero length"),
+        pytest.param(
+            b"\x01\x00\x03\x16",
+            mqtt5.MalformedPacketError,
+            "Invalid length",
+            id="Three-byte length too short",
+        ),
+        pytest.param(
+            b"\x02\x03",
+            mqtt5.MalformedPacketError,
+            "Invalid PacketType value: 3",
+            id="Reserved type",
+        ),
+        pytest.param(
+            b"\x02\xfe",
+            mqtt5.MalformedPacketError,
+            "Invalid PacketType value: 254",
+            id="Encapsulated message",
+        ),
+        pytest.param(
+            b"\x04\x17\x00\x00",
+            mqtt5.MalformedPacketError,
+            "Invalid length",
+            id="PingResp: Trailing bytes",
+        ),
+        pytest.param(
+            b"\x03\x0f\x00",
+            mqtt5.MalformedPacketError,
+            "Invalid remaining length",
+            id="PubRec: Packet ID cut short",
+        ),
+        pytest.param(
+            b"\x07\x04\x04\x02\x00\x3cfoo",
+            mqtt5.ProtocolError,
+            "Invalid protocol ID",
+            id="Connect: Invalid protocol ID",
+        ),
+        pytest.param(
+            b"\x06\x04\x04\x01\x00\x3c",
+            mqtt5.ProtocolError,
+            "Client id must have 1 to 23 characters",
+            id="Connect: Empty client id",
+        ),
+        pytest.param(
+            b"\x05\x16\xff\xfe\xfd",
+            mqtt5.MalformedPacketError,
+            "Invalid UTF-8 string",
+            id="PingReq: Invalid UTF-8 client id",
+        ),
+        pytest.param(
+            b"\x08\x0c\x03\x00\x05\x00\x00a",
+            mqtt5.MalformedPacketError,
+            "Invalid TopicIdType value: 3",
+            id="Publish: Reserved topic ID type",
+        ),
+        pytest.param(
+            b"\x08\x0c\x60\x00\x05\x00\x00a",
+            mqtt5.ProtocolError,
+            "QoS=-1 requires a predefined topic ID or a short topic name",
+            id="Publish: QoS=-1 with normal topic ID",
+        ),
+        pytest.param(
+            b"\x08\x12\x60\x00\x01foo",
+            mqtt5.MalformedPacketError,
+            "Invalid QoS value: 3",
+            id="Subscribe: QoS=-1",
+        ),
+        pytest.param(
+            b"\x03\x05\x04",
+            mqtt5.MalformedPacketError,
+            "Invalid ReturnCode value: 4",
+            id="ConnAck: Invalid return code",
+        ),
+    ],
+)
+def test_read_malformed_bytes_sn(
+    buffer: bytes, error: type[mqtt5.MQTTError], message: str
+) -> None:
+    """Test error from reading malformed MQTT-SN messages."""
+    with pytest.raises(error, match=f"^{re.escape(message)}$"):
+        mqtt5.sn.read(memoryview(buffer))
+
+
+@pytest.mark.parametrize(
+    ("cls", "args", "message"),
+    [
+        pytest.param(
+            mqtt5.sn.ConnectPacket,
+            {"client_id": "a" * 24},
+            "Client id must have 1 to 23 characters",
+            id="Connect: Client id too long",
+        ),
+        pytest.param(
+            mqtt5.sn.PingReqPacket,
+            {"client_id": ""},
+            "Client id must have 1 to 23 characters",
+            id="PingReq: Empty client id",
+        ),
+        pytest.param(
+            mqtt5.sn.WillTopicPacket,
+            {"retain": True},
+            "QoS and retain must not be set without topic",
+            id="WillTopic: Retain without topic",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket,
+            {"topic": "foo", "payload": b""},
+            "Topic must be a topic ID for topic ID type NORMAL",
+            id="Publish: Topic name with normal topic ID type",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket,
+            {
+                "topic": 5,
+                "payload": b"",
+                "topic_id_type": mqtt5.sn.TopicIdType.SHORT_NAME,
+            },
+            "Topic must be a topic name for topic ID type SHORT_NAME",
+            id="Publish: Topic ID with short topic name type",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket,
+            {
+                "topic": "foo",
+                "payload": b"",
+                "topic_id_type": mqtt5.sn.TopicIdType.SHORT_NAME,
+            },
+            "Short topic name must have 2 bytes",
+            id="Publish: Short topic name with 3 bytes",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket,
+            {"topic": 5, "payload": b"", "qos": None},
+            "QoS=-1 requires a predefined topic ID or a short topic name",
+            id="Publish: QoS=-1 with normal topic ID",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket,
+            {"topic": 5, "payload": b"", "packet_id": 1},
+            "Packet ID must not be set for QoS=0 and QoS=-1",
+            id="Publish: Packet ID with QoS=0",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket,
+            {"topic": 5, "payload": b"", "qos": mqtt5.QoS.EXACTLY_ONCE},
+            "Packet ID must be set for QoS=1 and QoS=2",
+            id="Publish: No packet ID with QoS=2",
+        ),
+        pytest.param(
+            mqtt5.sn.SubscribePacket,
+            {"packet_id": 1, "topic": 5},
+            "Topic must be a topic name for topic ID type NORMAL",
+            id="Subscribe: Topic ID with normal topic ID type",
+        ),
+        pytest.param(
+            mqtt5.sn.UnsubscribePacket,
+            {
+                "packet_id": 1,
+                "topic": "foo",
+                "topic_id_type": mqtt5.sn.TopicIdType.PREDEFINED,
+            },
+            "Topic must be a topic ID for topic ID type PREDEFINED",
+            id="Unsubscribe: Topic name with predefined topic ID type",
+        ),
+    ],
+)
+def test_invalid_arguments_sn(cls: type, args: dict, message: str) -> None:
+    """Test error from initializing MQTT-SN messages with invalid arguments."""
+    with pytest.raises(ValueError, match=f"^{re.escape(message)}$"):
+        cls(**args)
+
+
+@pytest.mark.parametrize(
+    "make",
+    [
+        pytest.param(
+            lambda s: mqtt5.sn.ConnectPacket(client_id=s), id="Connect: Client id"
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.WillTopicPacket(topic=s), id="WillTopic: Topic"
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.RegisterPacket(topic_id=1, packet_id=1, topic=s),
+            id="Register: Topic",
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.PublishPacket(
+                topic=s, payload=b"", topic_id_type=mqtt5.sn.TopicIdType.SHORT_NAME
+            ),
+            id="Publish: Short topic name",
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.SubscribePacket(packet_id=1, topic=s),
+            id="Subscribe: Topic name",
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.UnsubscribePacket(packet_id=1, topic=s),
+            id="Unsubscribe: Topic name",
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.PingReqPacket(client_id=s), id="PingReq: Client id"
+        ),
+        pytest.param(
+            lambda s: mqtt5.sn.WillTopicUpdPacket(topic=s), id="WillTopicUpd: Topic"
+        ),
+    ],
+)
+def test_null_character_parity_sn(
+    make: typing.Callable[[str], mqtt5.sn.Packet],
+) -> None:
+    """Test that MQTT-SN constructors reject U+0000 in strings like read does."""
+    data = make("\x7f\x7f").write()
+    assert data.count(b"\x7f\x7f") == 1
+    message = "^Invalid character in string$"
+    with pytest.raises(mqtt5.MalformedPacketError, match=message):
+        make("\x7f\x00")
+    with pytest.raises(mqtt5.MalformedPacketError, match=message):
+        mqtt5.sn.read(memoryview(data.replace(b"\x7f\x7f", b"\x7f\x00")))
+
+
+def test_write_too_large_sn() -> None:
+    """Test error from writing an MQTT-SN message that exceeds the three-byte length."""
+    packet = mqtt5.sn.WillMsgPacket(payload=bytes(65532))
+    with pytest.raises(mqtt5.PacketTooLargeError, match="^Message size exceeds 65535 bytes$"):
+        packet.write()
+    assert len(mqtt5.sn.WillMsgPacket(payload=bytes(65531)).write()) == 65535
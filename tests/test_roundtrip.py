@@ -1,5 +1,11 @@
 """Tests write/read (roundtrip) consistency."""
 
+import concurrent.futures
+import importlib
+import inspect
+import pickle
+import typing
+
 import conftest
 import pytest
 
@@ -14,3 +20,779 @@ def test_roundtrip(packet: mqtt5.Packet) -> None:
     assert nbytes == len(data)
     assert isinstance(packet2, type(packet))
     assert packet == packet2
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_nbytes(packet: mqtt5.Packet) -> None:
+    """Test that the size is equal to the length of the written packet."""
+    assert packet.nbytes() == len(packet.write())
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_write_into(packet: mqtt5.Packet) -> None:
+    """Test that writing into a buffer at an offset matches write()."""
+    data = packet.write()
+    buffer = bytearray(b"\xff" * (len(data) + 4))
+    nbytes = packet.write_into(memoryview(buffer), 3)
+    assert nbytes == len(data)
+    assert buffer == b"\xff" * 3 + data + b"\xff"
+
+
+@pytest.mark.parametrize("start", [0, 20, 100])
+def test_write_into_overlapping_payload(start: int) -> None:
+    """Test writing a packet into the buffer that its payload is a view into."""
+    buffer = bytearray(range(200))
+    payload = bytes(buffer[start : start + 50])
+    packet = mqtt5.PublishPacket(topic="t", payload=memoryview(buffer)[start : start + 50])
+    data = mqtt5.PublishPacket(topic="t", payload=payload).write()
+    assert packet.write_into(buffer, 10) == len(data)
+    assert buffer[10 : 10 + len(data)] == data
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_read_packet_is_constructible(packet: mqtt5.Packet) -> None:
+    """Test that a read packet passes the same validation as the constructor."""
+    packet2, _ = mqtt5.read(memoryview(packet.write()))
+    cls = type(packet2)
+    parameters = inspect.signature(cls).parameters
+    assert cls(**{name: getattr(packet2, name) for name in parameters}) == packet2
+
+
+@pytest.mark.parametrize("chunk_size", [1, 7, 2**16])
+def test_decoder_roundtrip(chunk_size: int) -> None:
+    """Test write/read consistency when decoding a stream in chunks."""
+    data = b"".join(packet.write() for packet in conftest.PACKETS)
+    decoder = mqtt5.Decoder()
+    packets = []
+    for index in range(0, len(data), chunk_size):
+        decoder.feed(data[index : index + chunk_size])
+        packets.extend(decoder)
+    assert packets == conftest.PACKETS
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_peek_header(packet: mqtt5.Packet) -> None:
+    """Test that the fixed header matches the written packet."""
+    data = memoryview(packet.write())
+    packet_type, flags, remaining_length, header_size = mqtt5.peek_header(data)
+    assert packet_type == data[0] >> 4
+    assert isinstance(packet_type, mqtt5.PacketType)
+    assert flags == data[0] & 0x0F
+    assert header_size + remaining_length == len(data)
+    for index in range(header_size):
+        assert mqtt5.peek_header(data[:index]) is None
+    assert mqtt5.peek_header(data[:header_size]) is not None
+
+
+@pytest.mark.parametrize(
+    ("version", "protocol_name"),
+    [
+        pytest.param(3, "MQIsdp", id="MQTT 3.1"),
+        pytest.param(4, "MQTT", id="MQTT 3.1.1"),
+        pytest.param(5, "MQTT", id="MQTT 5"),
+    ],
+)
+def test_sniff_connect(version: typing.Literal[3, 4, 5], protocol_name: str) -> None:
+    """Test that the protocol name and level are read from the start of a connect."""
+    data = memoryview(mqtt5.ConnectPacket(client_id="Bulbasaur").write(version=version))
+    # Fixed header, protocol name, and protocol level
+    start = 2 + 2 + len(protocol_name) + 1
+    for index in range(start, len(data) + 1):
+        assert mqtt5.sniff_connect(data[:index]) == (protocol_name, version)
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_roundtrip_without_payload_copy(packet: mqtt5.Packet) -> None:
+    """Test write/read consistency when payloads reference the read buffer."""
+    data = b"\x00" + packet.write()
+    packet2, nbytes = mqtt5.read(memoryview(data)[1:], copy_payload=False)
+    assert nbytes == len(data) - 1
+    assert packet == packet2
+    assert packet2.write() == data[1:]
+    if isinstance(packet2, mqtt5.PublishPacket):
+        assert isinstance(packet2.payload, memoryview)
+        assert packet2.payload.obj is data
+
+
+def test_payload_view_keeps_buffer_alive() -> None:
+    """Test that a payload view outlives the references to the read buffer."""
+    packet = mqtt5.PublishPacket(topic="foo", payload=b"bar")
+    buffer = bytearray(packet.write())
+    packet2, _ = mqtt5.read(memoryview(buffer), copy_payload=False)
+    # Exported buffers can't be resized while the payload view is alive
+    with pytest.raises(BufferError):
+        buffer.extend(b"\x00")
+    del buffer
+    assert packet2.payload == b"bar"
+
+
+def test_write_max_packet_size_reduction() -> None:
+    """Test that the reason string and then the user properties are dropped to fit."""
+    packet = mqtt5.PubAckPacket(
+        packet_id=1,
+        reason_str="Pikachu",
+        user_properties=[("type", "electric")],
+    )
+    data = packet.write()
+    assert packet.write(max_packet_size=len(data)) == data
+    packet2, _ = mqtt5.read(memoryview(packet.write(max_packet_size=len(data) - 1)))
+    assert packet2 == mqtt5.PubAckPacket(
+        packet_id=1, user_properties=[("type", "electric")]
+    )
+    packet2, _ = mqtt5.read(memoryview(packet.write(max_packet_size=4)))
+    assert packet2 == mqtt5.PubAckPacket(packet_id=1)
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_write_cache(packet: mqtt5.Packet) -> None:
+    """Test that written bytes are cached until a list of the packet changes."""
+    cls = type(packet)
+    args = {name: getattr(packet, name) for name in inspect.signature(cls).parameters}
+    for name, value in args.items():
+        if isinstance(value, list):
+            args[name] = list(value)
+    packet = cls(**args)
+    data = packet.write()
+    assert packet.write() is data
+    if hasattr(packet, "user_properties"):
+        packet.user_properties.append(("Pokémon", "Pikachu"))
+        assert packet.write() == cls(**args).write()
+        assert packet.write() != data
+
+
+def test_write_cache_read() -> None:
+    """Test that packets are written as the bytes they were read from until modified."""
+    # Properties in reverse order, which isn't how they are written
+    data = b"\x40\x11\x00\x01\x00\x0d\x26\x00\x01a\x00\x01b\x1f\x00\x03foo"
+    packet, _ = mqtt5.read(memoryview(data))
+    assert packet.write() == data
+    packet.user_properties.clear()
+    assert packet.write() == mqtt5.PubAckPacket(packet_id=1, reason_str="foo").write()
+
+
+@pytest.mark.parametrize(
+    "container", [bytearray, lambda data: memoryview(bytearray(data))]
+)
+def test_write_cache_mutable_payload(
+    container: typing.Callable[[bytes], bytearray | memoryview],
+) -> None:
+    """Test that packets with a mutable payload aren't written from the cache."""
+    payload = container(b"bar")
+    packet = mqtt5.PublishPacket(topic="foo", payload=payload)
+    assert packet.write() == mqtt5.PublishPacket(topic="foo", payload=b"bar").write()
+    payload[:] = b"baz"
+    assert packet.write() == mqtt5.PublishPacket(topic="foo", payload=b"baz").write()
+
+
+@pytest.mark.parametrize(
+    "container",
+    [
+        pytest.param(bytes, id="bytes"),
+        pytest.param(bytearray, id="bytearray"),
+        pytest.param(memoryview, id="memoryview of bytes"),
+        pytest.param(
+            lambda data: memoryview(bytearray(data)).toreadonly(),
+            id="Read-only memoryview of bytearray",
+        ),
+    ],
+)
+def test_read_large_threads(
+    container: typing.Callable[[bytes], bytes | bytearray | memoryview],
+) -> None:
+    """Test reading large packets concurrently, which releases the GIL while parsing."""
+    packet = mqtt5.SubscribePacket(
+        packet_id=1,
+        topic_filters=[mqtt5.TopicFilter(f"foo/{i}/#") for i in range(1000)],
+        user_properties=[(f"key{i}", f"value{i}") for i in range(100)],
+    )
+    data = container(packet.write())
+    with concurrent.futures.ThreadPoolExecutor(4) as executor:
+        results = list(executor.map(mqtt5.read, [data] * 16))
+    assert results == [(packet, len(data))] * 16
+
+
+def test_topic_cache() -> None:
+    """Test that repeated topics are read as the same string object."""
+    packet = mqtt5.PublishPacket(
+        topic="foo/bar", payload=b"", content_type="foo", response_topic="foo/bar"
+    )
+    data = packet.write() * 2
+    topic_cache = mqtt5.TopicCache(capacity=2)
+    packets, _ = mqtt5.read_many(memoryview(data), topic_cache=topic_cache)
+    assert packets == [packet, packet]
+    assert packets[0].topic is packets[1].topic
+    assert packets[0].topic is packets[0].response_topic
+    assert packets[0].content_type is packets[1].content_type
+    assert len(topic_cache) == 2
+    decoder = mqtt5.Decoder(topic_cache=topic_cache)
+    decoder.feed(data)
+    assert all(item.topic is packets[0].topic for item in decoder)
+    # The cache is cleared when it's full
+    packet2, _ = mqtt5.read(
+        memoryview(mqtt5.PublishPacket(topic="baz", payload=b"").write()),
+        topic_cache=topic_cache,
+    )
+    assert len(topic_cache) == 1
+    topic_cache.clear()
+    assert len(topic_cache) == 0
+
+
+def test_roundtrip_many() -> None:
+    """Test write/read consistency of a sequence of packets."""
+    data = mqtt5.write_many(conftest.PACKETS)
+    assert data == b"".join(packet.write() for packet in conftest.PACKETS)
+    packets, nbytes = mqtt5.read_many(memoryview(data))
+    assert nbytes == len(data)
+    assert packets == conftest.PACKETS
+
+
+@pytest.mark.parametrize("nbytes", [0, 1, 2, 3, 4, 5])
+def test_read_many_partial(nbytes: int) -> None:
+    """Test that reading many packets stops at a trailing partial packet."""
+    data = mqtt5.PingReqPacket().write() + mqtt5.PubAckPacket(packet_id=1).write()
+    packets, consumed = mqtt5.read_many(memoryview(data[:nbytes]))
+    assert consumed == (2 if nbytes >= 2 else 0)
+    assert packets == ([mqtt5.PingReqPacket()] if nbytes >= 2 else [])
+
+
+def test_write_vectored() -> None:
+    """Test that the vectored publish buffers join to the written packet."""
+    packet = mqtt5.PublishPacket(
+        topic="foo", payload=b"bar" * 100, qos=mqtt5.QoS.AT_LEAST_ONCE, packet_id=1
+    )
+    buffers = packet.write_vectored()
+    assert buffers[1] is packet.payload
+    assert b"".join(buffers) == packet.write()
+    assert packet.write_header() == buffers[0]
+
+
+@pytest.mark.parametrize("container", [bytearray, memoryview])
+def test_buffer_arguments(container: type) -> None:
+    """Test that binary arguments accept buffers other than bytes."""
+    payload = container(b"bar")
+    packet = mqtt5.PublishPacket(
+        topic="foo", payload=payload, correlation_data=container(b"baz")
+    )
+    assert packet.payload is payload
+    assert packet.correlation_data == b"baz"
+    assert isinstance(packet.correlation_data, bytes)
+    assert packet.write() == mqtt5.PublishPacket(
+        topic="foo", payload=b"bar", correlation_data=b"baz"
+    ).write()
+    packet2 = mqtt5.ConnectPacket(
+        client_id="foo",
+        password=container(b"bar"),
+        will=mqtt5.Will(topic="foo", payload=container(b"bar")),
+        authentication_method="foo",
+        authentication_data=container(b"bar"),
+    )
+    assert mqtt5.read(memoryview(packet2.write()))[0] == packet2
+
+
+def test_write_header_payload_nbytes() -> None:
+    """Test writing the header of a publish packet whose payload isn't in memory."""
+    packet = mqtt5.PublishPacket(topic="foo", payload=b"bar" * 100)
+    header = packet.write_header(payload_nbytes=300)
+    empty = mqtt5.PublishPacket(topic="foo", payload=b"")
+    data = empty.write_header(payload_nbytes=300)
+    assert header == data
+    assert header + packet.payload == packet.write()
+
+
+@pytest.mark.parametrize(
+    "packet",
+    [packet for packet in conftest.PACKETS if isinstance(packet, mqtt5.PublishPacket)],
+)
+@pytest.mark.parametrize("duplicate", [False, True])
+def test_publish_template(packet: mqtt5.PublishPacket, duplicate: bool) -> None:
+    """Test that packets encoded from a template match the written packets."""
+    cls = type(packet)
+    args = {name: getattr(packet, name) for name in inspect.signature(cls).parameters}
+    template = mqtt5.PublishTemplate(packet)
+    for payload in [b"", b"bar", bytes(1000)]:
+        expected = cls(**{**args, "payload": payload, "duplicate": duplicate}).write()
+        data = template.encode(payload, packet.packet_id, duplicate)
+        assert data == expected
+
+
+@pytest.mark.parametrize(
+    ("packet", "data"),
+    [
+        pytest.param(
+            mqtt5.ConnectPacket(client_id="Bulbasaur", clean_start=True, keep_alive=60),
+            b"\x10\x15\x00\x04MQTT\x04\x02\x00\x3c\x00\x09Bulbasaur",
+            id="Connect",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(
+                client_id="Bulbasaur",
+                username="foo",
+                password=b"bar",
+                will=mqtt5.Will(
+                    topic="foo", payload=b"bar", qos=mqtt5.QoS.AT_LEAST_ONCE
+                ),
+            ),
+            b"\x10\x29\x00\x04MQTT\x04\xcc\x00\x00\x00\x09Bulbasaur"
+            b"\x00\x03foo\x00\x03bar\x00\x03foo\x00\x03bar",
+            id="Connect: Will, username, and password",
+        ),
+        pytest.param(
+            mqtt5.ConnAckPacket(
+                session_present=True,
+                reason_code=mqtt5.ConnAckReasonCode.NOT_AUTHORIZED,
+            ),
+            b"\x20\x02\x01\x05",
+            id="ConnAck",
+        ),
+        pytest.param(
+            mqtt5.PublishPacket(
+                topic="foo", payload=b"bar", qos=mqtt5.QoS.AT_LEAST_ONCE, packet_id=1
+            ),
+            b"\x32\x0a\x00\x03foo\x00\x01bar",
+            id="Publish",
+        ),
+        pytest.param(mqtt5.PubAckPacket(packet_id=1), b"\x40\x02\x00\x01", id="PubAck"),
+        pytest.param(mqtt5.PubRelPacket(packet_id=1), b"\x62\x02\x00\x01", id="PubRel"),
+        pytest.param(
+            mqtt5.SubscribePacket(
+                packet_id=1,
+                topic_filters=[
+                    mqtt5.TopicFilter(pattern="foo/#", max_qos=mqtt5.QoS.AT_LEAST_ONCE)
+                ],
+            ),
+            b"\x82\x0a\x00\x01\x00\x05foo/#\x01",
+            id="Subscribe",
+        ),
+        pytest.param(
+            mqtt5.SubAckPacket(
+                packet_id=1,
+                reason_codes=[
+                    mqtt5.SubAckReasonCode.GRANTED_QOS_AT_LEAST_ONCE,
+                    mqtt5.SubAckReasonCode.UNSPECIFIED_ERROR,
+                ],
+            ),
+            b"\x90\x04\x00\x01\x01\x80",
+            id="SubAck",
+        ),
+        pytest.param(
+            mqtt5.UnsubscribePacket(packet_id=1, patterns=["foo/#"]),
+            b"\xa2\x09\x00\x01\x00\x05foo/#",
+            id="Unsubscribe",
+        ),
+        pytest.param(
+            mqtt5.UnsubAckPacket(packet_id=1, reason_codes=[]),
+            b"\xb0\x02\x00\x01",
+            id="UnsubAck",
+        ),
+        pytest.param(mqtt5.PingReqPacket(), b"\xc0\x00", id="PingReq"),
+        pytest.param(mqtt5.DisconnectPacket(), b"\xe0\x00", id="Disconnect"),
+    ],
+)
+def test_roundtrip_v311(packet: mqtt5.Packet, data: bytes) -> None:
+    """Test writing and reading packets in the MQTT 3.1.1 wire format."""
+    assert packet.write(version=4) == data
+    assert packet.nbytes(version=4) == len(data)
+    assert mqtt5.read(memoryview(data), version=4) == (packet, len(data))
+    decoder = mqtt5.Decoder(version=4)
+    decoder.feed(data * 2)
+    assert list(decoder) == [packet, packet]
+    assert mqtt5.write_many([packet, packet], version=4) == data * 2
+    # Packets that were read in one version are cached only for that version
+    packet2, _ = mqtt5.read(memoryview(data), version=4)
+    assert packet2.write() == packet.write()
+
+
+@pytest.mark.parametrize(
+    ("packet", "data"),
+    [
+        pytest.param(
+            mqtt5.ConnectPacket(client_id="Bulbasaur", clean_start=True, keep_alive=60),
+            b"\x10\x17\x00\x06MQIsdp\x03\x02\x00\x3c\x00\x09Bulbasaur",
+            id="Connect",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(client_id="a" * 23, username="foo", password=b"bar"),
+            b"\x10\x2f\x00\x06MQIsdp\x03\xc0\x00\x00\x00\x17"
+            + b"a" * 23
+            + b"\x00\x03foo\x00\x03bar",
+            id="Connect: Longest client id",
+        ),
+        pytest.param(
+            mqtt5.ConnAckPacket(reason_code=mqtt5.ConnAckReasonCode.NOT_AUTHORIZED),
+            b"\x20\x02\x00\x05",
+            id="ConnAck",
+        ),
+        pytest.param(
+            mqtt5.PublishPacket(
+                topic="foo", payload=b"bar", qos=mqtt5.QoS.AT_LEAST_ONCE, packet_id=1
+            ),
+            b"\x32\x0a\x00\x03foo\x00\x01bar",
+            id="Publish",
+        ),
+        pytest.param(
+            mqtt5.SubscribePacket(
+                packet_id=1,
+                topic_filters=[
+                    mqtt5.TopicFilter(pattern="foo/#", max_qos=mqtt5.QoS.AT_LEAST_ONCE)
+                ],
+            ),
+            b"\x82\x0a\x00\x01\x00\x05foo/#\x01",
+            id="Subscribe",
+        ),
+        pytest.param(mqtt5.DisconnectPacket(), b"\xe0\x00", id="Disconnect"),
+    ],
+)
+def test_roundtrip_v31(packet: mqtt5.Packet, data: bytes) -> None:
+    """Test writing and reading packets in the MQTT 3.1 wire format."""
+    assert packet.write(version=3) == data
+    assert packet.nbytes(version=3) == len(data)
+    assert mqtt5.read(memoryview(data), version=3) == (packet, len(data))
+    decoder = mqtt5.Decoder(version=3)
+    decoder.feed(data * 2)
+    assert list(decoder) == [packet, packet]
+
+
+@pytest.mark.parametrize("packet", conftest.PACKETS, ids=conftest.PACKET_NAMES)
+def test_downgrade(packet: mqtt5.Packet) -> None:
+    """Test that downgraded packets can be written and read in MQTT 3.1.1."""
+    if isinstance(packet, mqtt5.AuthPacket):
+        with pytest.raises(TypeError):
+            mqtt5.downgrade(packet)
+        return
+    downgraded, _ = mqtt5.downgrade(packet)
+    assert isinstance(downgraded, type(packet))
+    data = downgraded.write(version=4)
+    assert mqtt5.read(memoryview(data), version=4) == (downgraded, len(data))
+    assert mqtt5.downgrade(downgraded)[0] == downgraded
+
+
+@pytest.mark.parametrize(
+    ("packet", "expected", "lost"),
+    [
+        pytest.param(
+            mqtt5.ConnectPacket(
+                client_id="Bulbasaur",
+                password=b"foo",
+                clean_start=True,
+                will=mqtt5.Will(topic="foo", will_delay_interval=60),
+                session_expiry_interval=60,
+                receive_max=10,
+            ),
+            mqtt5.ConnectPacket(
+                client_id="Bulbasaur",
+                clean_start=True,
+                will=mqtt5.Will(topic="foo", payload=b""),
+            ),
+            [
+                "session_expiry_interval",
+                "password",
+                "will.will_delay_interval",
+                "receive_max",
+            ],
+            id="Connect",
+        ),
+        pytest.param(
+            mqtt5.ConnectPacket(
+                client_id="Bulbasaur",
+                clean_start=False,
+                session_expiry_interval=2**32 - 1,
+            ),
+            mqtt5.ConnectPacket(client_id="Bulbasaur", clean_start=False),
+            [],
+            id="Connect: Session without expiry",
+        ),
+        pytest.param(
+            mqtt5.ConnAckPacket(
+                reason_code=mqtt5.ConnAckReasonCode.BANNED, assigned_client_id="foo"
+            ),
+            mqtt5.ConnAckPacket(reason_code=mqtt5.ConnAckReasonCode.NOT_AUTHORIZED),
+            ["reason_code", "assigned_client_id"],
+            id="ConnAck: Banned",
+        ),
+        pytest.param(
+            mqtt5.ConnAckPacket(reason_code=mqtt5.ConnAckReasonCode.SERVER_BUSY),
+            mqtt5.ConnAckPacket(
+                reason_code=mqtt5.ConnAckReasonCode.SERVER_UNAVAILABLE
+            ),
+            ["reason_code"],
+            id="ConnAck: Server busy",
+        ),
+        pytest.param(
+            mqtt5.PublishPacket(
+                topic="foo", payload=b"bar", content_type="text/plain", topic_alias=1
+            ),
+            mqtt5.PublishPacket(topic="foo", payload=b"bar"),
+            ["content_type"],
+            id="Publish",
+        ),
+        pytest.param(
+            mqtt5.PubRecPacket(
+                packet_id=1, reason_code=mqtt5.PubRecReasonCode.QUOTA_EXCEEDED
+            ),
+            mqtt5.PubRecPacket(packet_id=1),
+            ["reason_code"],
+            id="PubRec: Quota exceeded",
+        ),
+        pytest.param(
+            mqtt5.SubscribePacket(
+                packet_id=1,
+                topic_filters=[
+                    mqtt5.TopicFilter(pattern="foo", no_local=True),
+                    mqtt5.TopicFilter(pattern="bar", no_local=True),
+                ],
+            ),
+            mqtt5.SubscribePacket(
+                packet_id=1,
+                topic_filters=[
+                    mqtt5.TopicFilter(pattern="foo"),
+                    mqtt5.TopicFilter(pattern="bar"),
+                ],
+            ),
+            ["topic_filters.no_local"],
+            id="Subscribe: No local",
+        ),
+        pytest.param(
+            mqtt5.SubAckPacket(
+                packet_id=1,
+                reason_codes=[
+                    mqtt5.SubAckReasonCode.GRANTED_QOS_AT_LEAST_ONCE,
+                    mqtt5.SubAckReasonCode.NOT_AUTHORIZED,
+                ],
+            ),
+            mqtt5.SubAckPacket(
+                packet_id=1,
+                reason_codes=[
+                    mqtt5.SubAckReasonCode.GRANTED_QOS_AT_LEAST_ONCE,
+                    mqtt5.SubAckReasonCode.UNSPECIFIED_ERROR,
+                ],
+            ),
+            ["reason_codes"],
+            id="SubAck: Not authorized",
+        ),
+        pytest.param(
+            mqtt5.UnsubAckPacket(
+                packet_id=1, reason_codes=[mqtt5.UnsubAckReasonCode.SUCCESS]
+            ),
+            mqtt5.UnsubAckPacket(packet_id=1, reason_codes=[]),
+            [],
+            id="UnsubAck",
+        ),
+        pytest.param(
+            mqtt5.DisconnectPacket(
+                reason_code=mqtt5.DisconnectReasonCode.DISCONNECT_WITH_WILL_MESSAGE
+            ),
+            mqtt5.DisconnectPacket(),
+            ["reason_code"],
+            id="Disconnect: With will message",
+        ),
+    ],
+)
+def test_downgrade_lost(
+    packet: mqtt5.Packet, expected: mqtt5.Packet, lost: list[str]
+) -> None:
+    """Test that downgrading reports the values that MQTT 3.1.1 can't express."""
+    assert mqtt5.downgrade(packet, topic_aliases={}) == (expected, lost)
+
+
+def test_downgrade_topic_alias() -> None:
+    """Test that topic aliases are resolved with the mapping of the connection."""
+    topic_aliases: dict[int, str] = {}
+    packet = mqtt5.PublishPacket(topic="foo", payload=b"bar", topic_alias=1)
+    downgraded, _ = mqtt5.downgrade(packet, topic_aliases=topic_aliases)
+    assert downgraded.topic == "foo"
+    assert downgraded.topic_alias is None
+    assert downgraded.payload is packet.payload
+    assert topic_aliases == {1: "foo"}
+    packet = mqtt5.PublishPacket(topic="", payload=bytearray(b"baz"), topic_alias=1)
+    downgraded, _ = mqtt5.downgrade(packet, topic_aliases=topic_aliases)
+    assert downgraded.write(version=4) == b"\x30\x08\x00\x03foobaz"
+
+
+def test_downgrade_topic_alias_without_mapping() -> None:
+    """Test that packets with a topic and an alias are downgraded without a mapping."""
+    packet = mqtt5.PublishPacket(topic="foo", payload=b"bar", topic_alias=1)
+    downgraded, lost = mqtt5.downgrade(packet)
+    assert downgraded == mqtt5.PublishPacket(topic="foo", payload=b"bar")
+    assert lost == []
+
+
+@pytest.mark.parametrize(
+    ("clean_start", "session_expiry_interval"),
+    [
+        pytest.param(True, 0, id="Clean session"),
+        pytest.param(False, 2**32 - 1, id="Persistent session"),
+    ],
+)
+def test_upgrade(clean_start: bool, session_expiry_interval: int) -> None:
+    """Test that upgrading fills the session expiry that the clean session implies."""
+    packet = mqtt5.ConnectPacket(client_id="Bulbasaur", clean_start=clean_start)
+    upgraded = mqtt5.upgrade(packet)
+    assert upgraded.session_expiry_interval == session_expiry_interval
+    assert mqtt5.downgrade(upgraded) == (packet, [])
+    other = mqtt5.PublishPacket(topic="foo", payload=b"bar")
+    assert mqtt5.upgrade(other) is other
+
+
+
+@pytest.mark.parametrize(
+    ("packet", "data"),
+    [
+        pytest.param(
+            mqtt5.sn.AdvertisePacket(gateway_id=1, duration=900),
+            b"\x05\x00\x01\x03\x84",
+            id="Advertise",
+        ),
+        pytest.param(mqtt5.sn.SearchGwPacket(radius=1), b"\x03\x01\x01", id="SearchGw"),
+        pytest.param(mqtt5.sn.GwInfoPacket(gateway_id=1), b"\x03\x02\x01", id="GwInfo"),
+        pytest.param(
+            mqtt5.sn.GwInfoPacket(gateway_id=1, gateway_address=b"\x7f\x00\x00\x01"),
+            b"\x07\x02\x01\x7f\x00\x00\x01",
+            id="GwInfo: Gateway address",
+        ),
+        pytest.param(
+            mqtt5.sn.ConnectPacket(
+                client_id="Bulbasaur", duration=60, clean_session=True, will=True
+            ),
+            b"\x0f\x04\x0c\x01\x00\x3cBulbasaur",
+            id="Connect",
+        ),
+        pytest.param(
+            mqtt5.sn.ConnAckPacket(return_code=mqtt5.sn.ReturnCode.REJECTED_CONGESTION),
+            b"\x03\x05\x01",
+            id="ConnAck",
+        ),
+        pytest.param(mqtt5.sn.WillTopicReqPacket(), b"\x02\x06", id="WillTopicReq"),
+        pytest.param(
+            mqtt5.sn.WillTopicPacket(topic="foo", qos=mqtt5.QoS.AT_LEAST_ONCE, retain=True),
+            b"\x06\x07\x30foo",
+            id="WillTopic",
+        ),
+        pytest.param(mqtt5.sn.WillTopicPacket(), b"\x02\x07", id="WillTopic: Empty"),
+        pytest.param(mqtt5.sn.WillMsgReqPacket(), b"\x02\x08", id="WillMsgReq"),
+        pytest.param(mqtt5.sn.WillMsgPacket(payload=b"bar"), b"\x05\x09bar", id="WillMsg"),
+        pytest.param(
+            mqtt5.sn.RegisterPacket(topic_id=0, packet_id=1, topic="foo/bar"),
+            b"\x0d\x0a\x00\x00\x00\x01foo/bar",
+            id="Register",
+        ),
+        pytest.param(
+            mqtt5.sn.RegAckPacket(topic_id=5, packet_id=1),
+            b"\x07\x0b\x00\x05\x00\x01\x00",
+            id="RegAck",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket(
+                topic=5, payload=b"bar", qos=mqtt5.QoS.AT_LEAST_ONCE, packet_id=2
+            ),
+            b"\x0a\x0c\x20\x00\x05\x00\x02bar",
+            id="Publish",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket(
+                topic=7,
+                payload=b"bar",
+                topic_id_type=mqtt5.sn.TopicIdType.PREDEFINED,
+                retain=True,
+            ),
+            b"\x0a\x0c\x11\x00\x07\x00\x00bar",
+            id="Publish: Predefined topic ID",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket(
+                topic="ab",
+                payload=b"bar",
+                topic_id_type=mqtt5.sn.TopicIdType.SHORT_NAME,
+                qos=None,
+            ),
+            b"\x0a\x0c\x62ab\x00\x00bar",
+            id="Publish: QoS=-1 with short topic name",
+        ),
+        pytest.param(
+            mqtt5.sn.PublishPacket(topic=5, payload=b"a" * 300),
+            b"\x01\x01\x35\x0c\x00\x00\x05\x00\x00" + b"a" * 300,
+            id="Publish: Three-byte length",
+        ),
+        pytest.param(
+            mqtt5.sn.PubAckPacket(
+                topic_id=5,
+                packet_id=2,
+                return_code=mqtt5.sn.ReturnCode.REJECTED_INVALID_TOPIC_ID,
+            ),
+            b"\x07\x0d\x00\x05\x00\x02\x02",
+            id="PubAck",
+        ),
+        pytest.param(mqtt5.sn.PubCompPacket(packet_id=2), b"\x04\x0e\x00\x02", id="PubComp"),
+        pytest.param(mqtt5.sn.PubRecPacket(packet_id=2), b"\x04\x0f\x00\x02", id="PubRec"),
+        pytest.param(mqtt5.sn.PubRelPacket(packet_id=2), b"\x04\x10\x00\x02", id="PubRel"),
+        pytest.param(
+            mqtt5.sn.SubscribePacket(
+                packet_id=3, topic="foo/#", qos=mqtt5.QoS.EXACTLY_ONCE, duplicate=True
+            ),
+            b"\x0a\x12\xc0\x00\x03foo/#",
+            id="Subscribe",
+        ),
+        pytest.param(
+            mqtt5.sn.SubscribePacket(
+                packet_id=3, topic=7, topic_id_type=mqtt5.sn.TopicIdType.PREDEFINED
+            ),
+            b"\x07\x12\x01\x00\x03\x00\x07",
+            id="Subscribe: Predefined topic ID",
+        ),
+        pytest.param(
+            mqtt5.sn.SubAckPacket(topic_id=5, packet_id=3, qos=mqtt5.QoS.EXACTLY_ONCE),
+            b"\x08\x13\x40\x00\x05\x00\x03\x00",
+            id="SubAck",
+        ),
+        pytest.param(
+            mqtt5.sn.UnsubscribePacket(
+                packet_id=4, topic="ab", topic_id_type=mqtt5.sn.TopicIdType.SHORT_NAME
+            ),
+            b"\x07\x14\x02\x00\x04ab",
+            id="Unsubscribe",
+        ),
+        pytest.param(mqtt5.sn.UnsubAckPacket(packet_id=4), b"\x04\x15\x00\x04", id="UnsubAck"),
+        pytest.param(mqtt5.sn.PingReqPacket(), b"\x02\x16", id="PingReq"),
+        pytest.param(
+            mqtt5.sn.PingReqPacket(client_id="Bulbasaur"),
+            b"\x0b\x16Bulbasaur",
+            id="PingReq: Client id",
+        ),
+        pytest.param(mqtt5.sn.PingRespPacket(), b"\x02\x17", id="PingResp"),
+        pytest.param(mqtt5.sn.DisconnectPacket(), b"\x02\x18", id="Disconnect"),
+        pytest.param(
+            mqtt5.sn.DisconnectPacket(duration=600),
+            b"\x04\x18\x02\x58",
+            id="Disconnect: Duration",
+        ),
+        pytest.param(
+            mqtt5.sn.WillTopicUpdPacket(topic="foo"),
+            b"\x06\x1a\x00foo",
+            id="WillTopicUpd",
+        ),
+        pytest.param(mqtt5.sn.WillTopicRespPacket(), b"\x03\x1b\x00", id="WillTopicResp"),
+        pytest.param(
+            mqtt5.sn.WillMsgUpdPacket(payload=bytearray(b"bar")),
+            b"\x05\x1cbar",
+            id="WillMsgUpd",
+        ),
+        pytest.param(mqtt5.sn.WillMsgRespPacket(), b"\x03\x1d\x00", id="WillMsgResp"),
+    ],
+)
+def test_roundtrip_sn(packet: mqtt5.sn.Packet, data: bytes) -> None:
+    """Test writing and reading messages in the MQTT-SN wire format."""
+    assert packet.write() == data
+    packet2, nbytes = mqtt5.sn.read(memoryview(data + b"\x02\x17"))
+    assert nbytes == len(data)
+    assert isinstance(packet2, type(packet))
+    assert packet == packet2
+    for index in range(len(data)):
+        with pytest.raises(mqtt5.IncompleteError):
+            mqtt5.sn.read(memoryview(data)[:index])
+
+
+def test_module_sn() -> None:
+    """Test that the MQTT-SN submodule can be found by its qualified name."""
+    assert mqtt5.sn.__name__ == "mqtt5.sn"
+    assert importlib.import_module("mqtt5.sn") is mqtt5.sn
+    cls = mqtt5.sn.PingReqPacket
+    assert pickle.loads(pickle.dumps(cls)) is cls